@@ -0,0 +1,212 @@
+//the batched simd paths against their scalar fallback and against the code from before them
+//before is the old [f32; 4] math kept below, it did less work per vertex and per pixel (no tangents, no renormalizing)
+//the old rasterizer drew straight into an SDL canvas so it has no headless before row
+//cargo bench --bench simd
+extern crate rust3denginetest;
+
+use rust3denginetest::depth::{DepthFunc, DepthMode};
+use rust3denginetest::render::ScreenTri;
+use rust3denginetest::simd::{self, Span, SpanEnd, SpanPixels, LANES};
+use rust3denginetest::{Camera, ColorSpace, Engine, FrameTarget, Light, Mat4, Point3, Renderer, Rgba, SimdLevel, Texture, Tri3d, Vec3, Vertex};
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+//fastest of ten runs, per iteration
+fn time<F: FnMut()>(iters: u32, mut f: F) -> Duration {
+    f();
+    (0..10)
+        .map(|_| {
+            let start = Instant::now();
+            for _ in 0..iters {
+                f();
+            }
+            start.elapsed() / iters
+        })
+        .min()
+        .unwrap()
+}
+
+//speedups over the Scalar level, then over before when there is one
+fn report(name: &str, before: Option<Duration>, base: Duration, runs: &[(SimdLevel, Duration)]) {
+    let x = |a: Duration, b: Duration| format!("{:.2}x", a.as_secs_f64() / b.as_secs_f64());
+    println!("{}", name);
+    if let Some(b) = before {
+        println!("  {:<8} {:>10.3?}", "before", b);
+    }
+    for (level, t) in std::iter::once((SimdLevel::Scalar, base)).chain(runs.iter().copied()) {
+        let old = before.map(|b| x(b, t)).unwrap_or_default();
+        println!("  {:<8} {:>10.3?}  {:>6}  {:>6}", format!("{:?}", level), t, x(base, t), old);
+    }
+}
+
+//the per triangle math the engine used before the series, trimmed to what the benches run
+mod before {
+    pub type V = [f32; 4];
+
+    #[inline]
+    pub fn multiply_mat(v: V, m: [[f32; 4]; 4]) -> V {
+        [
+            v[0] * m[0][0] + v[1] * m[1][0] + v[2] * m[2][0] + v[3] * m[3][0],
+            v[0] * m[0][1] + v[1] * m[1][1] + v[2] * m[2][1] + v[3] * m[3][1],
+            v[0] * m[0][2] + v[1] * m[1][2] + v[2] * m[2][2] + v[3] * m[3][2],
+            v[0] * m[0][3] + v[1] * m[1][3] + v[2] * m[2][3] + v[3] * m[3][3],
+        ]
+    }
+    #[inline]
+    fn scale_c(v: V, s: f32) -> V {
+        [v[0] * s, v[1] * s, v[2] * s, v[3]]
+    }
+    #[inline]
+    fn add(v: V, a: V) -> V {
+        [v[0] + a[0], v[1] + a[1], v[2] + a[2], v[3]]
+    }
+
+    #[derive(Copy, Clone)]
+    pub struct Tri {
+        pub ps: [V; 3],
+        pub ns: [V; 3],
+    }
+
+    //Tri3d::multiply_mat
+    pub fn transform(t: &Tri, m: [[f32; 4]; 4]) -> Tri {
+        Tri { ps: t.ps.map(|p| multiply_mat(p, m)), ns: t.ns.map(|n| multiply_mat(n, m)) }
+    }
+
+    //what draw_triangle worked out for every pixel of a span before shading it
+    pub fn span(tex: [[f32; 3]; 2], norm: [V; 2], point: [V; 2], depth: &[f32], ax: i32, bx: i32, acc: &mut f32) {
+        let tstep = 1.0 / (bx - ax) as f32;
+        for x in ax..bx {
+            let t = (x - ax) as f32 * tstep;
+            let tex_w = (1.0 - t) * tex[0][2] + t * tex[1][2];
+            if tex_w >= depth[x as usize % depth.len()] {
+                let u = ((1.0 - t) * tex[0][0] + t * tex[1][0]) / tex_w;
+                let v = ((1.0 - t) * tex[0][1] + t * tex[1][1]) / tex_w;
+                let n = add(scale_c(norm[0], 1.0 - t), scale_c(norm[1], t));
+                let p = scale_c(add(scale_c(point[0], 1.0 - t), scale_c(point[1], t)), 1.0 / tex_w);
+                *acc += u + v + n[0] + p[0];
+            }
+        }
+    }
+}
+
+//every level the cpu can run, narrowest first
+fn levels() -> Vec<SimdLevel> {
+    [SimdLevel::Scalar, SimdLevel::Sse2, SimdLevel::Avx]
+        .iter()
+        .copied()
+        .filter(|&l| l <= SimdLevel::detect())
+        .collect()
+}
+
+fn main() {
+    let mut seed = 1u32;
+    let mut rnd = move || {
+        seed = seed.wrapping_mul(1664525).wrapping_add(1013904223);
+        (seed >> 8) as f32 / (1u32 << 24) as f32
+    };
+    println!("detected {:?}", SimdLevel::detect());
+
+    //vertex transform, 60000 vertices into world space
+    let vertices: Vec<Vertex> = (0..60000)
+        .map(|_| Vertex {
+            pos: Point3::new(rnd() * 10.0, rnd() * 10.0, rnd() * 10.0),
+            uv: [rnd(), rnd(), 1.0],
+            normal: Vec3::new(0.3, 0.8, -0.5).normalize(),
+            tangent: Vec3::X,
+            bitangent: Vec3::Y,
+            col: Rgba::WHITE,
+        })
+        .collect();
+    let m = Mat4::rotation_y(0.4) * Mat4::translation(Vec3::new(1.0, 2.0, 3.0));
+    let transform = |level: SimdLevel| {
+        time(20, || {
+            black_box(simd::transform_vertices(level, &vertices, m, m));
+        })
+    };
+    let old: Vec<before::Tri> = vertices
+        .chunks(3)
+        .map(|c| {
+            let v = |p: [f32; 3], w| [p[0], p[1], p[2], w];
+            before::Tri { ps: [0, 1, 2].map(|i| v(c[i].pos.to_array(), 1.0)), ns: [0, 1, 2].map(|i| v(c[i].normal.to_array(), 1.0)) }
+        })
+        .collect();
+    let old_time = time(20, || {
+        black_box(old.iter().map(|t| before::transform(t, m.m)).collect::<Vec<_>>());
+    });
+    let base = transform(SimdLevel::Scalar);
+    let runs: Vec<_> = levels().into_iter().skip(1).map(|l| (l, transform(l))).collect();
+    report("transform 60000 vertices", Some(old_time), base, &runs);
+
+    //span interpolation, 100000 pixels
+    let mode = DepthMode { func: DepthFunc::LessEqual, reversed: false, near: 0.5, far: 250.0 };
+    let depth = vec![1.0; LANES];
+    let mut end = || ([rnd(), rnd(), rnd() + 0.1], Vec3::new(rnd(), rnd(), 1.0), Vec3::new(rnd(), rnd(), rnd()));
+    let (a, b) = (end(), end());
+    let new_end = |(uvw, n, p): ([f32; 3], Vec3, Vec3)| SpanEnd::new(uvw, n, Vec3::X, p, Vec3::new(1.0, 1.0, 1.0) * uvw[2]);
+    let span = Span { start: new_end(a), end: new_end(b), step: 1.0 / 100000.0 };
+    let interpolate = |level: SimdLevel| {
+        time(20, || {
+            let mut px = SpanPixels::default();
+            let mut acc = 0.0;
+            for x in (0..100000).step_by(LANES) {
+                span.interpolate(level, x as f32, black_box(&depth), &mode, &mut px);
+                acc += px.u[0];
+            }
+            black_box(acc);
+        })
+    };
+    let v = |v: Vec3| [v.x, v.y, v.z, 1.0];
+    let old_time = time(20, || {
+        let mut acc = 0.0;
+        before::span([a.0, b.0], [v(a.1), v(b.1)], [v(a.2), v(b.2)], black_box(&depth), 0, 100000, &mut acc);
+        black_box(acc);
+    });
+    let base = interpolate(SimdLevel::Scalar);
+    let runs: Vec<_> = levels().into_iter().skip(1).map(|l| (l, interpolate(l))).collect();
+    report("interpolate 100000 pixels", Some(old_time), base, &runs);
+
+    //whole rasterizer on one thread, 150 lit triangles into 640x480, lighting is most of its time
+    let tex = Texture::new(2, 2, 6, 3, vec![200, 100, 50, 10, 20, 30, 90, 90, 90, 255, 0, 0], ColorSpace::Srgb);
+    let cam = Camera {
+        fov: 90.0,
+        pos: Point3::ORIGIN,
+        dir: Vec3::Z,
+        vel: Vec3::ZERO,
+        rot_vel: Vec3::ZERO,
+        clip_distance: 0.5,
+        render_distance: 250.0,
+        window_height: 480.0,
+        window_width: 640.0,
+    };
+    let mut engine = Engine::new(cam);
+    engine.lights.push(Light::point(Point3::new(0.0, 0.0, -2.0), Rgba::WHITE, 100.0));
+    let screen: Vec<(Tri3d, Tri3d)> = (0..150)
+        .map(|_| {
+            let z = 1.0 + rnd() * 20.0;
+            let mut p = || Point3::new(rnd() * 640.0, rnd() * 480.0, 0.5);
+            let mut t = Tri3d::new([p(), p(), p()], [[0.0, 0.0, 1.0], [1.0, 0.0, 1.0], [1.0, 1.0, 1.0]], [Vec3::new(0.0, 0.0, -1.0); 3], Rgba::RED, 0.5, 0.0);
+            let mut info = t;
+            info.ps = [Point3::new(0.0, 0.0, z); 3];
+            t.uvs = t.uvs.map(|uv| [uv[0] / z, uv[1] / z, 1.0 / z]);
+            (t, info)
+        })
+        .collect();
+    let st: Vec<ScreenTri> = screen
+        .iter()
+        .map(|&(tri, info)| ScreenTri { tri, info, tex: &tex, material: None, normal_map: None, depth_write: true })
+        .collect();
+    let mut target = FrameTarget::new(640, 480);
+    let mut draw = |level: SimdLevel| {
+        let mut r = Renderer::new(Vec3::Y);
+        r.threads = 1;
+        r.simd = level;
+        time(3, || {
+            target.clear();
+            r.rasterize(&engine, &st, &mut target);
+        })
+    };
+    let base = draw(SimdLevel::Scalar);
+    let runs: Vec<_> = levels().into_iter().skip(1).map(|l| (l, draw(l))).collect();
+    report("rasterize 150 triangles", None, base, &runs);
+}
+
@@ -0,0 +1,277 @@
+//when shipping game, make sure you got everything in the folder with the gameeextern crate sdl2;
+extern crate gl;
+extern crate sdl2;
+extern crate rust3denginetest;
+
+use sdl2::pixels;
+use sdl2::image; 
+use pixels::{Color};
+use sdl2::pixels::PixelFormatEnum;
+use sdl2::render::TextureAccess;
+use sdl2::render::{WindowCanvas};
+use sdl2::event::{Event};
+use sdl2::keyboard::{Keycode};
+use sdl2::gfx::framerate::FPSManager;
+use sdl2::gfx::primitives::DrawRenderer;
+
+use rust3denginetest::world;
+use rust3denginetest::{Engine, Mesh, Camera, Point3, Vec3, Light, Renderer, FrameTarget, Rgba, Node, Quat, Transform};
+
+//pub const RES_MOD : i32 = 4;
+fn main() {
+
+    let world_up = Vec3::Y;
+    let mut fps_manager = FPSManager::new();
+
+    let sdl_context = sdl2::init().unwrap();
+    
+    let video_subsystem = sdl_context.video().unwrap();
+    let _audio_subsystem = sdl_context.audio().unwrap();
+    let _sdl_image_context = image::init(image::InitFlag::all());
+
+    let window = video_subsystem.window("game", 800, 750)
+        //.fullscreen_desktop()
+        .build()
+    .map_err(|e| e.to_string()).unwrap();
+
+    
+    let mut canvas : WindowCanvas = window
+        .into_canvas()
+        .build()
+        .map_err(|e| e.to_string())
+    .unwrap();
+    
+    let texture_creator = canvas.texture_creator();
+    let screen_width = canvas.output_size().unwrap().0 as i32;
+    let screen_height = canvas.output_size().unwrap().1 as i32;
+    let mut event_pump = sdl_context.event_pump().unwrap();
+    let _texture_creator = canvas.texture_creator();
+    let max_fps = 60_u32;
+    let player_cam = Camera{
+        fov : 90.0,
+        pos : Point3::new(10.0, 0.0, 5.0),
+        dir : Vec3::new(-1.0, 0.0, 0.0),
+        vel : Vec3::ZERO,
+        rot_vel : Vec3::ZERO,
+        clip_distance : 0.5,
+        render_distance : 250.0,
+        window_height : screen_height as f32,
+        window_width : screen_width as f32,
+        
+    };
+
+
+    
+    let mut engine = Engine::new(player_cam);
+    engine.ambient = Rgba::rgb(0.01, 0.01, 0.012);
+    let renderer = Renderer::new(world_up);
+    let mut frame = FrameTarget::new(screen_width as usize, screen_height as usize);
+    let mut ring_buffer = [
+        texture_creator.create_texture(PixelFormatEnum::RGB24, TextureAccess::Streaming, screen_width as u32, screen_height as u32).unwrap(),
+        texture_creator.create_texture(PixelFormatEnum::RGB24, TextureAccess::Streaming, screen_width as u32, screen_height as u32).unwrap(),
+        texture_creator.create_texture(PixelFormatEnum::RGB24, TextureAccess::Streaming, screen_width as u32, screen_height as u32).unwrap(),
+        texture_creator.create_texture(PixelFormatEnum::RGB24, TextureAccess::Streaming, screen_width as u32, screen_height as u32).unwrap(),
+    ];
+    let ring_buffer_length = 1;
+    let mut index = 0;
+
+
+    engine.objects.push(Mesh::load_obj_file("assets/normalized_teapot.obj".to_string(),"assets/white.png".to_string(), Rgba::RED, 1.0, 0.0).unwrap());
+    engine.objects.push(Mesh::load_obj_file("assets/real_sphere.obj".to_string(),"assets/white.png".to_string(), Rgba::WHITE, 1.0, 0.5).unwrap());
+    world::estimate_normals(&mut engine.objects[1]);
+    
+    engine.objects.push(Mesh::load_obj_file("assets/normalized_cube.obj".to_string(),"assets/white.png".to_string(), Rgba::WHITE, 0.0, 0.0).unwrap());
+
+    //the sphere is a child of the teapot, it follows wherever the teapot goes
+    let teapot = engine.scene.add(Node { transform: Transform::from_pos(Point3::new(0.0, 0.0, 5.0)), ..Node::with_mesh("teapot", 0) }, None);
+    engine.scene.add(Node { transform: Transform::from_pos(Point3::new(6.0, 0.0, 0.0)), ..Node::with_mesh("sphere", 1) }, Some(teapot));
+    let wall = Transform::new(Point3::new(-5.0, 0.0, 5.0), Quat::IDENTITY, Vec3::new(1.0, 10.0, 10.0));
+    engine.scene.add(Node { transform: wall, ..Node::with_mesh("wall", 2) }, None);
+    //engine.objects[0].rot_vel = Vec3::new(45_f32.to_radians(), 90_f32.to_radians(), 0.0);
+
+    
+    engine.lights.push(
+        Light::sun(
+            Rgba::WHITE, 
+            Vec3::new(-1.0, -0.3, 0.0).normalize(),
+            3,
+            60.0,
+        )
+    );
+    
+
+
+
+    let cspeed = 10.0;
+    
+    let rspeed = 60.0_f32.to_radians();
+    
+    let mouse = sdl_context.mouse();
+    
+    //mouse.show_cursor(false);
+    'running: loop {
+
+
+
+        let fps = fps_manager.get_framerate() as f32;
+        engine.camera.rot_vel = Vec3::ZERO;
+        for event in event_pump.poll_iter() {
+            match event {
+                Event::Quit {..} |
+                Event::KeyDown { keycode: Some(Keycode::Escape), .. } => {
+                    break 'running
+                },
+                Event::KeyDown {keycode: Some(Keycode::W), .. } => {
+                    engine.camera.vel.z = 1.0;
+                }, Event::KeyUp {keycode: Some(Keycode::W), .. } => {
+                    engine.camera.vel.z = 0.0;
+                },
+                
+                Event::KeyDown {keycode: Some(Keycode::A), .. } => {
+                    engine.camera.vel.x = 1.0;
+                }, Event::KeyUp {keycode: Some(Keycode::A), .. } => {
+                    engine.camera.vel.x = 0.0;
+                },
+                
+                Event::KeyDown {keycode: Some(Keycode::S), .. } => {
+                    engine.camera.vel.z = -1.0;
+                }, Event::KeyUp {keycode: Some(Keycode::S), .. } => {
+                    engine.camera.vel.z = 0.0;
+                },
+                
+                Event::KeyDown {keycode: Some(Keycode::D), .. } => {
+                    engine.camera.vel.x = -1.0;
+                }, Event::KeyUp {keycode: Some(Keycode::D), .. } => {
+                    engine.camera.vel.x = 0.0;
+                },
+                
+                Event::KeyDown {keycode: Some(Keycode::E), .. } => {
+                    engine.camera.vel.y = 1.0;
+                }, Event::KeyUp {keycode: Some(Keycode::E), .. } => {
+                    engine.camera.vel.y = 0.0;
+                },
+                
+                Event::KeyDown {keycode: Some(Keycode::Q), .. } => {
+                    engine.camera.vel.y = -1.0;
+                }, Event::KeyUp {keycode: Some(Keycode::Q), .. } => {
+                    engine.camera.vel.y = 0.0;
+                },
+
+                
+                
+                //--------------ROTATE--------------
+                Event::KeyDown {keycode: Some(Keycode::Up), .. } => {
+                    engine.camera.rot_vel.x = 1.0;
+                }, Event::KeyUp {keycode: Some(Keycode::Up), .. } => {
+                    engine.camera.rot_vel.x = 0.0;
+                },
+                
+                Event::KeyDown {keycode: Some(Keycode::Down), .. } => {
+                    engine.camera.rot_vel.x = -1.0;
+                }, Event::KeyUp {keycode: Some(Keycode::Down), .. } => {
+                    engine.camera.rot_vel.x = 0.0;
+                },
+                
+                Event::KeyDown {keycode: Some(Keycode::Left), .. } => {
+                    engine.camera.rot_vel.y = -1.0;
+                }, Event::KeyUp {keycode: Some(Keycode::Left), .. } => {
+                    engine.camera.rot_vel.y = 0.0;
+                },
+                
+                Event::KeyDown {keycode: Some(Keycode::Right), .. } => {
+                    engine.camera.rot_vel.y = 1.0;
+                }, Event::KeyUp {keycode: Some(Keycode::Right), .. } => {
+                    engine.camera.rot_vel.y = 0.0;
+                },
+                
+
+            
+                Event::MouseMotion {x, y, ..} => {
+                    
+                    let win = canvas.window();
+                    let s = canvas.output_size().unwrap();
+                    mouse.warp_mouse_in_window(win, s.0 as i32/2, s.1 as i32/2);
+                    engine.camera.rot_vel.x += ((y-s.1 as i32/2) as f32).to_radians(); 
+                    engine.camera.rot_vel.y += ((x-s.0 as i32/2) as f32).to_radians();
+                    
+                },
+                
+                _ => {}
+            }
+        }
+        //The rest of the game loop goes here...
+        //ok
+        
+        
+        //update camera
+        let cam = &mut engine.camera;
+        
+        {
+            cam.turn(cam.rot_vel.y*rspeed/fps, cam.rot_vel.x*rspeed/fps, world_up);
+            let cam_fwd = cam.dir;
+            let cam_up = (world_up - cam.dir*world_up.dot(cam.dir)).normalize();
+            let cam_right = cam.dir.cross(cam_up).normalize();
+            
+            let mvel = Vec3::new(
+                cam.vel.dot(cam_right), 
+                cam.vel.dot(cam_up),
+                cam.vel.dot(cam_fwd),
+            )*(cspeed/fps);
+            cam.pos += mvel;
+        }
+        
+        //moving the node leaves the mesh's vertices as they were loaded
+        let objects = &engine.objects;
+        for node in engine.scene.nodes_mut(){
+            if let Some(obj) = node.mesh.map(|i| &objects[i]){
+                let t = &mut node.transform;
+                t.pos += obj.vel*(1.0/fps);
+                let spin = obj.rot_vel*(1.0/fps);
+                t.rot = (t.rot*Quat::from_euler(spin.x, spin.y, spin.z)).normalize();
+            }
+        }
+
+        renderer.render(&mut engine, &mut frame).unwrap();
+
+        let current_tex = &mut ring_buffer[index];
+        index = (index+1)%ring_buffer_length;
+        
+        current_tex.update(None, frame.color.as_slice(), (3*screen_width) as usize).unwrap();
+
+        canvas.copy(current_tex, None, None).unwrap();
+
+        fps_manager.set_framerate(max_fps).unwrap();
+        let del = fps_manager.delay();
+        //out of range when the frame took over a second, the max_fps rate set above stays then
+        if let Some(rate) = 1000u32.checked_div(del){
+            fps_manager.set_framerate(rate).ok();
+        }
+        let c = (fps_manager.get_framerate() as f32/max_fps as f32*255.0) as u8;
+        canvas.string(
+            5,
+            5,
+            &format!("fps: {}", fps_manager.get_framerate()).to_string(),
+            Color::RGB(255, c, c)
+        ).unwrap();
+
+
+        canvas.string(
+            5,
+            25,
+            &format!("pos: (x: {}, y: {}, z: {})", engine.camera.pos.x, engine.camera.pos.y, engine.camera.pos.z).to_string(),
+            Color::WHITE
+        ).unwrap();
+
+        canvas.string(
+            5,
+            45,
+            &format!("dir: (x: {}, y: {}, z: {})", engine.camera.dir.x, engine.camera.dir.y, engine.camera.dir.z).to_string(),
+            Color::WHITE
+        ).unwrap();
+        
+        canvas.present();
+        canvas.set_draw_color(Color::BLACK);
+        canvas.clear();
+
+    }
+}
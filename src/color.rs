@@ -1,90 +1,224 @@
 use sdl2::pixels::Color;
 use crate::ops::{clamp};
-pub trait ColFuncs {
-    fn blend_with_alpha(&self, c: Self) -> Self;
-    fn blend(&self, c: Self) -> Self;
-    fn avg(&self, c: Self) -> Self;
-    fn scale(&self, s: f32) -> Self;
-    fn avg_f32(&self, s: f32) -> Self;
-    fn add(&self, c: Self) -> Self;
-    fn from_f32_greyscale(f: f32) -> Self;
+use std::ops::{Add, AddAssign, Div, Mul, MulAssign, Sub};
+use std::sync::OnceLock;
+
+//linear rgb with straight alpha, channels are not clamped so values above 1 carry hdr light
+#[derive(Copy, Clone, Debug, PartialEq, Default)]
+pub struct Rgba {
+    pub r: f32,
+    pub g: f32,
+    pub b: f32,
+    pub a: f32,
 }
 
-impl ColFuncs for Color {
-    #[inline]
-    fn blend_with_alpha(&self, c: Self) -> Self {
+impl Rgba {
+    pub const BLACK: Rgba = Rgba::rgb(0.0, 0.0, 0.0);
+    pub const WHITE: Rgba = Rgba::rgb(1.0, 1.0, 1.0);
+    pub const RED: Rgba = Rgba::rgb(1.0, 0.0, 0.0);
+    pub const GREEN: Rgba = Rgba::rgb(0.0, 1.0, 0.0);
+    pub const BLUE: Rgba = Rgba::rgb(0.0, 0.0, 1.0);
+
+    pub const fn new(r: f32, g: f32, b: f32, a: f32) -> Self {
+        Rgba { r, g, b, a }
+    }
+    pub const fn rgb(r: f32, g: f32, b: f32) -> Self {
+        Rgba { r, g, b, a: 1.0 }
+    }
+    pub const fn grey(v: f32) -> Self {
+        Rgba::rgb(v, v, v)
+    }
+    //decodes an 8 bit srgb color, alpha is already linear
+    pub fn from_srgb(c: Color) -> Self {
+        Rgba::new(srgb_to_linear(c.r), srgb_to_linear(c.g), srgb_to_linear(c.b), c.a as f32 / 255.0)
+    }
+    //encodes to 8 bit srgb, clamping to 0..1 first
+    pub fn to_srgb(self) -> Color {
         Color::RGBA(
-            (self.r as f32 * (c.r as f32 / 255.0)) as u8,
-            (self.g as f32 * (c.g as f32 / 255.0)) as u8,
-            (self.b as f32 * (c.b as f32 / 255.0)) as u8,
-            (self.a as f32 * (c.a as f32 / 255.0)) as u8,
+            linear_to_srgb(self.r),
+            linear_to_srgb(self.g),
+            linear_to_srgb(self.b),
+            (clamp(self.a, 0.0, 1.0) * 255.0 + 0.5) as u8,
         )
     }
-    #[inline] 
-    fn blend(&self, c: Self) -> Self{
-        Color::RGB(
-            (self.r as f32 * (c.r as f32 / 255.0)) as u8,
-            (self.g as f32 * (c.g as f32 / 255.0)) as u8,
-            (self.b as f32 * (c.b as f32 / 255.0)) as u8,
-        )
+    #[inline]
+    pub fn lerp(self, o: Rgba, t: f32) -> Self {
+        self + (o - self) * t
     }
+    //rgb scaled by alpha, the form blending and filtering want
     #[inline]
-    fn avg(&self, c: Self) -> Self {
-        Color::RGBA(
-            self.r/2 + c.r/2,
-            self.g/2 + c.g/2,
-            self.b/2 + c.b/2,            
-            self.a/2 + c.a/2,
-        )
+    pub fn premultiply(self) -> Self {
+        Rgba::new(self.r * self.a, self.g * self.a, self.b * self.a, self.a)
     }
     #[inline]
-    fn scale(&self, s: f32) -> Self {
-        Color::RGBA(
-            (self.r as f32 * s) as u8,
-            (self.g as f32 * s) as u8,
-            (self.b as f32 * s) as u8,
-            (self.a as f32 * s) as u8,
-        )
+    pub fn unpremultiply(self) -> Self {
+        if self.a <= 0.0 {
+            return Rgba::new(0.0, 0.0, 0.0, 0.0);
+        }
+        let inv = 1.0 / self.a;
+        Rgba::new(self.r * inv, self.g * inv, self.b * inv, self.a)
+    }
+    //premultiplied `self` over premultiplied `dst`
+    #[inline]
+    pub fn over(self, dst: Rgba) -> Self {
+        self + dst * (1.0 - self.a)
+    }
+    #[inline]
+    pub fn luminance(self) -> f32 {
+        0.2126 * self.r + 0.7152 * self.g + 0.0722 * self.b
     }
+    #[inline]
+    pub fn map(self, f: impl Fn(f32) -> f32) -> Self {
+        Rgba::new(f(self.r), f(self.g), f(self.b), self.a)
+    }
+    //hue in degrees 0..360, saturation and value 0..1, taken from the channels as they are
+    pub fn to_hsv(self) -> (f32, f32, f32) {
+        let max = self.r.max(self.g).max(self.b);
+        let min = self.r.min(self.g).min(self.b);
+        let d = max - min;
+        let h = if d <= 0.0 {
+            0.0
+        } else if max == self.r {
+            60.0 * ((self.g - self.b) / d).rem_euclid(6.0)
+        } else if max == self.g {
+            60.0 * ((self.b - self.r) / d + 2.0)
+        } else {
+            60.0 * ((self.r - self.g) / d + 4.0)
+        };
+        let s = if max <= 0.0 { 0.0 } else { d / max };
+        (h, s, max)
+    }
+    pub fn from_hsv(h: f32, s: f32, v: f32, a: f32) -> Self {
+        let c = v * s;
+        let h = h.rem_euclid(360.0) / 60.0;
+        let x = c * (1.0 - (h % 2.0 - 1.0).abs());
+        let (r, g, b) = match h as u32 {
+            0 => (c, x, 0.0),
+            1 => (x, c, 0.0),
+            2 => (0.0, c, x),
+            3 => (0.0, x, c),
+            4 => (x, 0.0, c),
+            _ => (c, 0.0, x),
+        };
+        let m = v - c;
+        Rgba::new(r + m, g + m, b + m, a)
+    }
+}
 
+//all operators work on every channel including alpha
+impl Add for Rgba {
+    type Output = Rgba;
     #[inline]
-    fn avg_f32(&self, s: f32) -> Self {
-        let c = (s * 127.0) as u8;
-        Color::RGBA(
-            self.r/2 + c,
-            self.g/2 + c,
-            self.b/2 + c,            
-            self.a/2 + c,
-        )
+    fn add(self, o: Rgba) -> Rgba {
+        Rgba::new(self.r + o.r, self.g + o.g, self.b + o.b, self.a + o.a)
+    }
+}
+
+impl AddAssign for Rgba {
+    #[inline]
+    fn add_assign(&mut self, o: Rgba) {
+        *self = *self + o;
     }
+}
 
+impl Sub for Rgba {
+    type Output = Rgba;
     #[inline]
-    fn add(&self, c: Self) -> Self {
-        Color::RGBA(
-            clamp(self.r as u16 + c.r as u16, 0, 255) as u8,
-            clamp(self.g as u16 + c.g as u16, 0, 255) as u8, 
-            clamp(self.b as u16 + c.b as u16, 0, 255) as u8, 
-            clamp(self.a as u16 + c.a as u16, 0, 255) as u8
-        )
+    fn sub(self, o: Rgba) -> Rgba {
+        Rgba::new(self.r - o.r, self.g - o.g, self.b - o.b, self.a - o.a)
     }
+}
 
-    fn from_f32_greyscale(f: f32) -> Self {
-        let f = (f * 255.0) as u8;
-        Color::RGBA(f, f, f, f)
+impl Mul for Rgba {
+    type Output = Rgba;
+    #[inline]
+    fn mul(self, o: Rgba) -> Rgba {
+        Rgba::new(self.r * o.r, self.g * o.g, self.b * o.b, self.a * o.a)
     }
 }
+
+impl MulAssign for Rgba {
+    #[inline]
+    fn mul_assign(&mut self, o: Rgba) {
+        *self = *self * o;
+    }
+}
+
+impl Mul<f32> for Rgba {
+    type Output = Rgba;
+    #[inline]
+    fn mul(self, s: f32) -> Rgba {
+        Rgba::new(self.r * s, self.g * s, self.b * s, self.a * s)
+    }
+}
+
+impl MulAssign<f32> for Rgba {
+    #[inline]
+    fn mul_assign(&mut self, s: f32) {
+        *self = *self * s;
+    }
+}
+
+impl Div<f32> for Rgba {
+    type Output = Rgba;
+    #[inline]
+    fn div(self, s: f32) -> Rgba {
+        self * (1.0 / s)
+    }
+}
+
+//8 bit srgb channel to linear 0..1, through a table since every texel goes through it
+#[inline]
+pub fn srgb_to_linear(c: u8) -> f32 {
+    static TABLE: OnceLock<[f32; 256]> = OnceLock::new();
+    TABLE.get_or_init(|| {
+        let mut t = [0.0; 256];
+        for (i, v) in t.iter_mut().enumerate() {
+            *v = srgb_decode(i as f32 / 255.0);
+        }
+        t
+    })[c as usize]
+}
+
+//srgb encoded 0..1 to linear, for values that were filtered before decoding
+#[inline]
+pub fn srgb_decode(c: f32) -> f32 {
+    if c <= 0.04045 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) }
+}
+
+//linear 0..1 to srgb encoded 0..1, the inverse of srgb_decode
+#[inline]
+pub fn srgb_encode(v: f32) -> f32 {
+    if v <= 0.003_130_8 { v * 12.92 } else { 1.055 * v.powf(1.0 / 2.4) - 0.055 }
+}
+
+//linear 0..1 to 8 bit srgb, values outside are clamped
 #[inline]
-pub fn avg_cols(cols : &[Color]) -> Color{
-    let mut a : usize = 0;
-    let mut b : usize = 0;
-    let mut g : usize = 0;
-    let mut r : usize = 0;
-    for c in cols{
-        r = r + c.r as usize;
-        g = g + c.g as usize;
-        b = b + c.b as usize;
-        a = a + c.a as usize;
-    }
-    let ln = cols.len();
-    Color::RGBA((r/ln) as u8, (g/ln) as u8, (b/ln) as u8, (a/ln) as u8)
+pub fn linear_to_srgb(v: f32) -> u8 {
+    (srgb_encode(clamp(v, 0.0, 1.0)) * 255.0 + 0.5) as u8
+}
+
+//maps linear hdr values into 0..1 before they are encoded
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ToneMap {
+    //cuts everything above 1
+    Clamp,
+    //x / (1 + x)
+    Reinhard,
+    //narkowicz's fit of the aces filmic curve
+    Aces,
+    //1 - e^-x
+    Exposure,
+}
+
+impl ToneMap {
+    #[inline]
+    pub fn apply(self, x: f32) -> f32 {
+        match self {
+            ToneMap::Clamp => clamp(x, 0.0, 1.0),
+            ToneMap::Reinhard => x / (1.0 + x),
+            ToneMap::Aces => clamp(x * (2.51 * x + 0.03) / (x * (2.43 * x + 0.59) + 0.14), 0.0, 1.0),
+            ToneMap::Exposure => 1.0 - (-x).exp(),
+        }
+    }
 }
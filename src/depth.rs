@@ -0,0 +1,169 @@
+//how a new depth value is compared against the stored one, true means the new fragment wins
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DepthFunc {
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    Always,
+}
+
+impl DepthFunc {
+    #[inline]
+    pub fn test(self, new: f32, old: f32) -> bool {
+        match self {
+            DepthFunc::Less => new < old,
+            DepthFunc::LessEqual => new <= old,
+            DepthFunc::Greater => new > old,
+            DepthFunc::GreaterEqual => new >= old,
+            DepthFunc::Always => true,
+        }
+    }
+}
+
+//everything needed to turn the rasterizer's 1/w into a stored depth and test it
+#[derive(Copy, Clone, Debug)]
+pub struct DepthMode {
+    pub func: DepthFunc,
+    pub reversed: bool,
+    pub near: f32,
+    pub far: f32,
+}
+
+impl DepthMode {
+    //value the buffer is cleared to, the far plane
+    pub fn far_value(&self) -> f32 {
+        if self.reversed {
+            0.0
+        } else {
+            1.0
+        }
+    }
+    //inv_w is 1/z in view space, which is linear across the screen
+    //normal maps near..far to 0..1, reversed maps it to 1..0 so floats keep their precision far away
+    #[inline]
+    pub fn value(&self, inv_w: f32) -> f32 {
+        let range = self.far - self.near;
+        if self.reversed {
+            self.near / range * (self.far * inv_w - 1.0)
+        } else {
+            self.far / range * (1.0 - self.near * inv_w)
+        }
+    }
+    #[inline]
+    pub fn test(&self, new: f32, old: f32) -> bool {
+        self.func.test(new, old)
+    }
+}
+
+pub struct DepthBuffer {
+    pub width: usize,
+    pub height: usize,
+    pub data: Vec<f32>,
+    pub mode: DepthMode,
+}
+
+impl DepthBuffer {
+    pub fn new(width: usize, height: usize) -> Self {
+        let mode = DepthMode {
+            func: DepthFunc::LessEqual,
+            reversed: false,
+            near: 0.5,
+            far: 250.0,
+        };
+        DepthBuffer {
+            width,
+            height,
+            data: vec![mode.far_value(); width * height],
+            mode,
+        }
+    }
+    //also flips the compare function so nearer fragments still win
+    pub fn set_reversed(&mut self, reversed: bool) {
+        if reversed != self.mode.reversed {
+            self.mode.func = match self.mode.func {
+                DepthFunc::Less => DepthFunc::Greater,
+                DepthFunc::LessEqual => DepthFunc::GreaterEqual,
+                DepthFunc::Greater => DepthFunc::Less,
+                DepthFunc::GreaterEqual => DepthFunc::LessEqual,
+                DepthFunc::Always => DepthFunc::Always,
+            };
+            self.mode.reversed = reversed;
+        }
+    }
+    pub fn set_range(&mut self, near: f32, far: f32) {
+        self.mode.near = near;
+        self.mode.far = far;
+    }
+    pub fn clear(&mut self) {
+        let v = self.mode.far_value();
+        self.clear_to(v);
+    }
+    pub fn clear_to(&mut self, value: f32) {
+        self.data.fill(value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn funcs() {
+        let cases = [
+            (DepthFunc::Less, [true, false, false]),
+            (DepthFunc::LessEqual, [true, true, false]),
+            (DepthFunc::Greater, [false, false, true]),
+            (DepthFunc::GreaterEqual, [false, true, true]),
+            (DepthFunc::Always, [true, true, true]),
+        ];
+        //new below, equal to and above the stored 0.5
+        for (func, want) in cases {
+            assert_eq!([func.test(0.25, 0.5), func.test(0.5, 0.5), func.test(0.75, 0.5)], want, "{:?}", func);
+        }
+    }
+
+    #[test]
+    fn value_range() {
+        let mut buf = DepthBuffer::new(2, 2);
+        buf.set_range(1.0, 100.0);
+        let m = buf.mode;
+        //inv_w is 1/z, near and far land on the ends
+        assert!(m.value(1.0).abs() < 1e-6);
+        assert!((m.value(0.01) - 1.0).abs() < 1e-6);
+        assert!(m.value(0.5) < m.value(0.1));
+        assert_eq!(m.far_value(), 1.0);
+        assert!(m.test(m.value(0.5), m.value(0.1)) && !m.test(m.value(0.1), m.value(0.5)));
+    }
+
+    #[test]
+    fn reversed() {
+        let mut buf = DepthBuffer::new(2, 2);
+        buf.set_range(1.0, 100.0);
+        buf.set_reversed(true);
+        let m = buf.mode;
+        assert_eq!(m.func, DepthFunc::GreaterEqual);
+        assert!((m.value(1.0) - 1.0).abs() < 1e-6);
+        assert!(m.value(0.01).abs() < 1e-6);
+        //nearer is larger now and still wins
+        let (near, far) = (m.value(0.5), m.value(0.1));
+        assert!(near > far);
+        assert!(m.test(near, far) && !m.test(far, near));
+        buf.clear();
+        assert!(buf.data.iter().all(|&d| d == 0.0));
+        //flipping twice, or to what it already is, gives the function back
+        buf.set_reversed(true);
+        assert_eq!(buf.mode.func, DepthFunc::GreaterEqual);
+        buf.set_reversed(false);
+        assert_eq!(buf.mode.func, DepthFunc::LessEqual);
+        buf.clear();
+        assert!(buf.data.iter().all(|&d| d == 1.0));
+    }
+
+    #[test]
+    fn clear_to() {
+        let mut buf = DepthBuffer::new(3, 2);
+        buf.clear_to(0.25);
+        assert_eq!(buf.data, vec![0.25; 6]);
+    }
+}
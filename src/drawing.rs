@@ -1,71 +1,101 @@
 use crate::world::{Engine};
-use crate::ColFuncs;
-use crate::{Tri3d, Vec3};
-use sdl2::rect::Rect;
-use sdl2::gfx::primitives::DrawRenderer;
-use sdl2::sys::SDL_ThreadFunction;
-use sdl2::{pixels::Color, render::WindowCanvas, surface::Surface, rect::Point, };
-use crate::light::{SHADOW_RESOLUTION, SPREAD_VAL};
+use crate::ops::Vec3;
+use sdl2::{pixels::Color, surface::Surface, rect::Point, };
+use crate::render::{ScreenTri, Tile};
+use crate::simd::{Span, SpanEnd, SpanPixels, LANES};
 use crate::ops::clamp;
-use crate::avg_cols;
-use std::convert::TryInto;
+use crate::color::{srgb_decode, Rgba};
+use crate::material::{rfl_roughness, Material};
+use crate::texture::Texture;
+use std::f32::consts::PI;
 use std::mem::swap;
-use std::thread;
-use std::time::{Duration, Instant};
+//depth weight for weighted blended order-independent transparency (mcguire & bavoil 2013, eq. 9)
+//nearer fragments dominate the average without needing the fragments sorted
+#[inline]
+pub fn oit_weight(view_z: f32) -> f32 {
+    clamp(10.0 / (1e-5 + (view_z / 5.0).powi(2) + (view_z / 200.0).powi(6)), 1e-2, 3e3)
+}
+
+//cook-torrance with a ggx distribution, schlick-smith geometry and schlick fresnel
+//n, v and l are unit vectors away from the surface, returns the brdf per channel
+#[inline]
+pub fn cook_torrance(n: Vec3, v: Vec3, l: Vec3, albedo: Rgba, metallic: f32, roughness: f32) -> Rgba {
+    let h = (v + l).normalize();
+    let ndl = n.dot(l).max(0.0);
+    let ndv = n.dot(v).max(1e-4);
+    let ndh = n.dot(h).max(0.0);
+    let vdh = v.dot(h).max(0.0);
+    //a perfect mirror would make d a spike no pixel ever hits
+    let roughness = clamp(roughness, 0.045, 1.0);
+    let a2 = roughness.powi(4);
+    let dd = ndh * ndh * (a2 - 1.0) + 1.0;
+    let d = a2 / (PI * dd * dd);
+    let k = (roughness + 1.0).powi(2) / 8.0;
+    let g = ndv / (ndv * (1.0 - k) + k) * ndl / (ndl * (1.0 - k) + k);
+    let fw = (1.0 - vdh).powi(5);
+    let channel = |albedo: f32| -> f32 {
+        //dielectrics reflect about 4%, metals tint the reflection and have no diffuse
+        let f0 = 0.04 + (albedo - 0.04) * metallic;
+        let f = f0 + (1.0 - f0) * fw;
+        let spec = d * g * f / (4.0 * ndv * ndl + 1e-4);
+        (1.0 - f) * (1.0 - metallic) * albedo / PI + spec
+    };
+    Rgba::rgb(channel(albedo.r), channel(albedo.g), channel(albedo.b))
+}
+
 pub trait DrawTri {
     fn textured_triangle(
         &mut self,
-        tri: Tri3d,
-        surf: &Surface,
-        engine: &mut Engine,
-        tri_info: Tri3d,
-        tex_buffer: &mut Vec<u8>
+        st: &ScreenTri,
+        engine: &Engine,
     );
 }
-impl DrawTri for WindowCanvas {
+impl DrawTri for Tile<'_> {
     #[inline]
     fn textured_triangle(
         &mut self,
-        tri: Tri3d,
-        surf: &Surface,
-        engine: &mut Engine,
-        tri_info: Tri3d,
-        tex_buffer: &mut Vec<u8>
+        st: &ScreenTri,
+        engine: &Engine,
     ) {
-        let iters = 16.0;
-
-        let POISSON_DISK = &[
-            [-0.942_016, -0.399_062],
-            [0.945_586, -0.768_907],
-            [-0.094_184, -0.929_388],
-            [0.344_959, 0.293_877],
-            [-0.915_885, 0.457_714],
-            [-0.815_442, -0.879_124],
-            [-0.382_775, 0.276_768],
-            [0.974_843, 0.756_483],
-            [0.443_233, -0.975_115],
-            [0.537_429, -0.473_734],
-            [-0.264_969, -0.418_930],
-            [0.791_975, 0.190_901],
-            [-0.241_888, 0.997_065],
-            [-0.814_099, 0.914_375],
-            [0.199_841, 0.786_413],
-            [0.143_831, -0.141_007],
-        ];
-        
-        //let start = Instant::now();
-        let mut add_col : Vec<Color> = vec![tri_info.col];
+        let tri = st.tri;
+        let tri_info = st.info;
+        let tex = st.tex;
+        //vertex colors multiply kd, meshes without materials carry their color on the vertices alone
+        let (kd, metallic, roughness, emissive) = match st.material {
+            Some(m) => (m.kd, m.metallic, m.roughness, m.emissive),
+            None => (Rgba::WHITE, 0.0, rfl_roughness(tri_info.rfl), Rgba::BLACK),
+        };
+        let cutout = st.material.and_then(|m| m.cutout(tex, &engine.textures, self.sampler));
+        let map = |f: fn(&Material) -> &Option<String>| {
+            st.material.and_then(|m| f(m).as_ref()).and_then(|p| engine.textures.get(p))
+        };
+        let roughness_map = map(|m| &m.map_pr);
+        let metallic_map = map(|m| &m.map_pm);
+        let emissive_map = map(|m| &m.map_ke);
+        let ambient = engine.ambient;
         let mut point = Point::new(0, 0);
+        let mut px = SpanPixels::default();
         let s = (
-            engine.camera.window_width as i32,
-            engine.camera.window_height as i32,
+            self.width as i32,
+            self.height as i32,
         );
-        let height = surf.height() as usize;
-        let width = surf.width() as usize;
-        let pitch = surf.pitch() as usize;
-        let buffer = surf.without_lock().unwrap();
         let ps = tri.ps;
         let uvs = tri.uvs;
+        //screen gradients of u/w, v/w and 1/w, constant over the triangle, for picking mip levels
+        let area = (ps[1].x - ps[0].x) * (ps[2].y - ps[0].y) - (ps[2].x - ps[0].x) * (ps[1].y - ps[0].y);
+        let grad = |k: usize| -> (f32, f32) {
+            if area.abs() < 1e-12 {
+                return (0.0, 0.0);
+            }
+            let (a2, a3) = (uvs[1][k] - uvs[0][k], uvs[2][k] - uvs[0][k]);
+            (
+                (a2 * (ps[2].y - ps[0].y) - a3 * (ps[1].y - ps[0].y)) / area,
+                (a3 * (ps[1].x - ps[0].x) - a2 * (ps[2].x - ps[0].x)) / area,
+            )
+        };
+        let (ux, uy) = grad(0);
+        let (vx, vy) = grad(1);
+        let (wx, wy) = grad(2);
 
 
         let mut c1 = ps[0];
@@ -77,30 +107,47 @@ impl DrawTri for WindowCanvas {
         let mut l1 = tri_info.ns[0];
         let mut l2 = tri_info.ns[1];
         let mut l3 = tri_info.ns[2];
-
-        let mut v1 = tri_info.ps[0];
-        let mut v2 = tri_info.ps[1];
-        let mut v3 = tri_info.ps[2];
-
-        if c1[1] > c2[1] {
+        let mut k1 = tri_info.ts[0];
+        let mut k2 = tri_info.ts[1];
+        let mut k3 = tri_info.ts[2];
+        //bitangents are rebuilt from normal and tangent, only their side is kept
+        let bsign = if tri_info.ts[0].cross(tri_info.ns[0]).dot(tri_info.bs[0]) < 0.0 { -1.0 } else { 1.0 };
+
+        //world positions over w, affine in screen space like u/w and v/w
+        let mut v1 = tri_info.ps[0].to_vec() * uvs[0][2];
+        let mut v2 = tri_info.ps[1].to_vec() * uvs[1][2];
+        let mut v3 = tri_info.ps[2].to_vec() * uvs[2][2];
+        //vertex colors over w the same way
+        let rgb = |c: Rgba, w: f32| Vec3::new(c.r, c.g, c.b) * w;
+        let mut q1 = rgb(tri_info.cols[0], uvs[0][2]);
+        let mut q2 = rgb(tri_info.cols[1], uvs[1][2]);
+        let mut q3 = rgb(tri_info.cols[2], uvs[2][2]);
+
+        if c1.y > c2.y {
             swap(&mut c1, &mut c2);
             swap(&mut i1, &mut i2);
             swap(&mut l1, &mut l2);
+            swap(&mut k1, &mut k2);
             swap(&mut v1, &mut v2);
+            swap(&mut q1, &mut q2);
         }
 
-        if c1[1] > c3[1] {
+        if c1.y > c3.y {
             swap(&mut c1, &mut c3);
             swap(&mut i1, &mut i3);
             swap(&mut l1, &mut l3);
+            swap(&mut k1, &mut k3);
             swap(&mut v1, &mut v3);
+            swap(&mut q1, &mut q3);
         }
 
-        if c2[1] > c3[1] {
+        if c2.y > c3.y {
             swap(&mut c2, &mut c3);
             swap(&mut i2, &mut i3);
             swap(&mut l2, &mut l3);
+            swap(&mut k2, &mut k3);
             swap(&mut v2, &mut v3);
+            swap(&mut q2, &mut q3);
         }
 
         let mut dax_step = 0.0;
@@ -116,67 +163,87 @@ impl DrawTri for WindowCanvas {
         let mut dv3_step = 0.0;
         let mut dw3_step = 0.0;
 
-        let mut dav_step = [0.0, 0.0, 0.0, 0.0];
-        let mut dbv_step = [0.0, 0.0, 0.0, 0.0];
-        let mut dcv_step = [0.0, 0.0, 0.0, 0.0];
+        let mut dav_step = Vec3::ZERO;
+        let mut dbv_step = Vec3::ZERO;
+        let mut dcv_step = Vec3::ZERO;
+
+        let mut la_step = Vec3::ZERO;
+        let mut lb_step = Vec3::ZERO;
+        let mut lc_step = Vec3::ZERO;
 
-        let mut la_step = [0.0, 0.0, 0.0, 1.0];
-        let mut lb_step = [0.0, 0.0, 0.0, 1.0];
-        let mut lc_step = [0.0, 0.0, 0.0, 1.0];
+        let mut ka_step = Vec3::ZERO;
+        let mut kb_step = Vec3::ZERO;
+        let mut kc_step = Vec3::ZERO;
 
-        let dya = c2[1] - c1[1];
-        let dyb = c3[1] - c1[1];
-        let dyc = c3[1] - c2[1];
+        let mut qa_step = Vec3::ZERO;
+        let mut qb_step = Vec3::ZERO;
+        let mut qc_step = Vec3::ZERO;
+
+        let dya = c2.y - c1.y;
+        let dyb = c3.y - c1.y;
+        let dyc = c3.y - c2.y;
 
         if dya != 0.0 {
             //point a to point b
             let da = 1.0 / dya;
-            dax_step = (c2[0] - c1[0]) * da;
+            dax_step = (c2.x - c1.x) * da;
             du1_step = (i2[0] - i1[0]) * da;
             dv1_step = (i2[1] - i1[1]) * da;
             dw1_step = (i2[2] - i1[2]) * da;
 
-            dav_step = v2.subtract(v1).scale_c(da);
-            la_step = l2.subtract(l1).scale_c(da);
+            dav_step = (v2 - v1) * da;
+            la_step = (l2 - l1) * da;
+            ka_step = (k2 - k1) * da;
+            qa_step = (q2 - q1) * da;
         }
 
         if dyb != 0.0 {
             //point a to point c
             let db = 1.0 / dyb;
-            dbx_step = (c3[0] - c1[0]) * db;
+            dbx_step = (c3.x - c1.x) * db;
             du2_step = (i3[0] - i1[0]) * db;
             dv2_step = (i3[1] - i1[1]) * db;
             dw2_step = (i3[2] - i1[2]) * db;
-            dbv_step = v3.subtract(v1).scale_c(db);
-            lb_step = l3.subtract(l1).scale_c(db);
+            dbv_step = (v3 - v1) * db;
+            lb_step = (l3 - l1) * db;
+            kb_step = (k3 - k1) * db;
+            qb_step = (q3 - q1) * db;
         };
 
         if dyc != 0.0 {
             //point b to point c
             let dc = 1.0 / dyc;
-            dcx_step = (c3[0] - c2[0]) * dc;
+            dcx_step = (c3.x - c2.x) * dc;
             du3_step = (i3[0] - i2[0]) * dc;
             dv3_step = (i3[1] - i2[1]) * dc;
             dw3_step = (i3[2] - i2[2]) * dc;
 
-            dcv_step = v3.subtract(v2).scale_c(dc);
-            lc_step = l3.subtract(l2).scale_c(dc);
+            dcv_step = (v3 - v2) * dc;
+            lc_step = (l3 - l2) * dc;
+            kc_step = (k3 - k2) * dc;
+            qc_step = (q3 - q2) * dc;
         }
         
-        for y in c1[1] as i32 + 1..c3[1] as i32 + 1 {
-            if y > 0 && y < s.1 {
+        //only the rows of this tile
+        //floor so rows just above the top edge round away from row 0
+        let y_start = (c1.y.floor() as i32 + 1).max(self.y0 as i32);
+        let y_end = (c3.y.floor() as i32 + 1).min((self.y0 + self.height) as i32);
+        for y in y_start..y_end {
+            if y >= 0 {
                 point.y = y;
                 let mut tex_s: [f32; 3];
 
-                let mut point_s: [f32; 4];
+                let mut point_s: Vec3;
 
                 let mut ax: i32;
                 
-                let mut ls: [f32; 4];
-                let ys1 = y as f32 - c1[1];
-                let ys2 = y as f32 - c2[1];
-                if y < c2[1] as i32 + 1 {
-                    ax = (c1[0] + (ys1) * dax_step) as i32;
+                let mut ls: Vec3;
+                let mut ks: Vec3;
+                let mut qs: Vec3;
+                let ys1 = y as f32 - c1.y;
+                let ys2 = y as f32 - c2.y;
+                if y < c2.y as i32 + 1 {
+                    ax = (c1.x + (ys1) * dax_step) as i32;
 
                     tex_s = [
                         i1[0] + (ys1) * du1_step,
@@ -184,11 +251,13 @@ impl DrawTri for WindowCanvas {
                         i1[2] + (ys1) * dw1_step,
                     ];
 
-                    ls = l1.add(la_step.scale_c(ys1));
+                    ls = l1 + la_step * ys1;
+                    ks = k1 + ka_step * ys1;
+                    qs = q1 + qa_step * ys1;
 
-                    point_s = v1.add(dav_step.scale_c(ys1));
+                    point_s = v1 + dav_step * ys1;
                 } else {
-                    ax = (c2[0] + (ys2) * dcx_step) as i32;
+                    ax = (c2.x + (ys2) * dcx_step) as i32;
                     
                     tex_s = [
                         i2[0] + (ys2) * du3_step,
@@ -196,12 +265,14 @@ impl DrawTri for WindowCanvas {
                         i2[2] + (ys2) * dw3_step,
                     ];
 
-                    ls = l2.add(lc_step.scale_c(ys2));
+                    ls = l2 + lc_step * ys2;
+                    ks = k2 + kc_step * ys2;
+                    qs = q2 + qc_step * ys2;
 
-                    point_s = v2.add(dcv_step.scale_c(ys2));
+                    point_s = v2 + dcv_step * ys2;
                 }
 
-                let mut bx = (c1[0] + (ys1) * dbx_step) as i32;
+                let mut bx = (c1.x + (ys1) * dbx_step) as i32;
 
                 let mut tex_e = [
                     i1[0] + (ys1) * du2_step,
@@ -209,127 +280,158 @@ impl DrawTri for WindowCanvas {
                     i1[2] + (ys1) * dw2_step,
                 ];
 
-                let mut le = l1.add(lb_step.scale_c(ys1));
+                let mut le = l1 + lb_step * ys1;
+                let mut ke = k1 + kb_step * ys1;
+                let mut qe = q1 + qb_step * ys1;
 
-                let mut point_e = v1.add(dbv_step.scale_c(ys1));
+                let mut point_e = v1 + dbv_step * ys1;
 
                 if ax > bx {
                     swap(&mut ax, &mut bx);
                     swap(&mut tex_s, &mut tex_e);
                     swap(&mut ls, &mut le);
+                    swap(&mut ks, &mut ke);
+                    swap(&mut qs, &mut qe);
                     swap(&mut point_s, &mut point_e);
                 }
-                let tstep = 1.0 / (bx - ax) as f32;
-
-                for x in ax..bx {
-                    if x > 0 && x < s.0 {
+                let span = Span {
+                    start: SpanEnd::new(tex_s, ls, ks, point_s, qs),
+                    end: SpanEnd::new(tex_e, le, ke, point_e, qe),
+                    step: 1.0 / (bx - ax) as f32,
+                };
+                let row = (s.0 * (y - self.y0 as i32)) as usize;
+                let (x0, x1) = (ax.max(0), bx.min(s.0));
+                //LANES pixels at a time, interpolated and depth tested together
+                for xc in (x0..x1).step_by(LANES) {
+                    let first = row + xc as usize;
+                    let count = ((x1 - xc) as usize).min(LANES);
+                    let depth = &self.depth[first..first + count];
+                    span.interpolate(self.simd, (xc - ax) as f32, depth, &self.depth_mode, &mut px);
+                    for i in 0..count {
+                        if px.mask & 1 << i == 0 {
+                            continue;
+                        }
+                        let x = xc + i as i32;
                         point.x = x;
-                        let t = (x - ax) as f32 * tstep;
-                        let tex_w = (1.0 - t) * tex_s[2] + t * tex_e[2];
-                        let dbi = (x + s.0 * y) as usize;
-                        if tex_w >= engine.depth_buffer[dbi]
-                            || engine.transparency_buffer[dbi].0 > 0.0
-                        {
-                            let tr_buf = engine.transparency_buffer[dbi];
-                            let d_buf = engine.depth_buffer[dbi];
-
-                            let ind = (pitch / width)
-                                * ((width as f32 - 0.1) * ((1.0 - t) * tex_s[0] + t * tex_e[0])
-                                    / tex_w) as usize
-                                + pitch
-                                    * ((height as f32 - 0.1)
-                                        * ((1.0 - t) * tex_s[1] + t * tex_e[1])
-                                        / tex_w) as usize;
-
-                            //note: col = (diff*cos_theta + spec*r^5)*shadow*light_color*light_power + ambient
-
-                            let col = if ind < buffer.len() - 2 {
-                                //let trs = buffer[ind].a as f32/255.0
-                                let norm = ls.scale_c(1.0 - t).add(le.scale_c(t));
-                                let point = point_s
-                                    .scale_c(1.0 - t)
-                                    .add(point_e.scale_c(t))
-                                    .scale_c(1.0 / tex_w);
-                                
-                                let cpoint = engine.camera.pos.subtract(point).normalize();
-                                
-                                for light in &engine.lights {
-                                    let dp = -norm.dot_product(light.dir);
-                                    
-                                    let r = [norm[0]*2.0*dp+light.dir[0], norm[1]*2.0*dp+light.dir[1], norm[2]*2.0*dp+light.dir[2], 1.0]
-                                        .dot_product(
-                                            cpoint
-                                        );
-                                    let g = { //shadows
-                                        //let dp1 = dp.powi(2);
-                                        //let b = clamp(0.005 * ((1.0-dp1)/dp1).sqrt(), 0.0, 0.01);
-                                    
-                                        let b = 0.005;
-                                        let t = point
-                                            .multiply_mat(light.look_mat)
-                                            .multiply_mat(light.proj_mat);
-                                
-                                        let t3 = 1.0 / (t[3] + 1.0);
-                                        let f0 = (t[0] * t3 + 1.0) * SHADOW_RESOLUTION.0 as f32 * 0.5;
-                                        let f1 = (t[1] * t3 + 1.0) * SHADOW_RESOLUTION.1 as f32 * 0.5;
-                                        let d_val = t[2] * t3;
-                                        let mut l = 0.0;
-                                        for item in POISSON_DISK { //make the loop customizable (1 to 16 iters)
-                                            let ind = (f0 + item[0] * SPREAD_VAL) as usize
-                                                + SHADOW_RESOLUTION.0
-                                                    * (f1 + item[1] * SPREAD_VAL) as usize;
-                                            if ind < light.buf.len()
-                                                && d_val - b <= light.buf[ind]
-                                                && d_val >= b
-                                            {
-                                                l += 1.0;
-                                            }
-                                        }
-                                        l/iters
-                                    };
-                                    add_col.push(
-                                        tri.col.scale(dp) //diff
-                                            .add(Color::from_f32_greyscale(tri.rfl*r.powi(5))) //modif
-                                        .scale(g).blend(light.col)
-                                    );
+                        let dbi = first + i;
+                        let (tex_w, z, u, v) = (px.inv_w[i], px.z[i], px.u[i], px.v[i]);
+                        //d(u/w)/dx = u*d(1/w)/dx + du/dx/w, solved for du/dx
+                        let iw = 1.0 / tex_w;
+                        let (dudx, dudy) = ((ux - u * wx) * iw, (uy - u * wy) * iw);
+                        let (dvdx, dvdy) = ((vx - v * wx) * iw, (vy - v * wy) * iw);
+                        //cutout, neither shaded nor written to depth
+                        if cutout.is_some_and(|c| c.discards(u, v, [dudx, dvdx, dudy, dvdy])) {
+                            continue;
+                        }
+                        let sample = |t: &Texture| self.sampler.sample(t, u, v, t.lod(dudx, dvdx, dudy, dvdy));
+                        let raw = sample(tex);
+                        //roughness and metallic maps hold linear values, emissive ones srgb colors
+                        let roughness = roughness_map.map_or(roughness, |t| roughness * sample(t).r);
+                        let metallic = metallic_map.map_or(metallic, |t| metallic * sample(t).r);
+                        let emissive = emissive_map.map_or(emissive, |t| {
+                            let e = sample(t);
+                            emissive * Rgba::rgb(srgb_decode(e.r), srgb_decode(e.g), srgb_decode(e.b))
+                        });
+                        let texel = Rgba::rgb(srgb_decode(raw.r), srgb_decode(raw.g), srgb_decode(raw.b));
+
+                        let col = {
+                            let mut norm = px.normal(i);
+                            if let Some(nm) = st.normal_map {
+                                let lod = nm.lod(dudx, dvdx, dudy, dvdy);
+                                let raw = self.sampler.sample(nm, u, v, lod);
+                                //green points up the image while v runs down it
+                                let nx = raw.r * 2.0 - 1.0;
+                                let ny = 1.0 - raw.g * 2.0;
+                                let nz = raw.b * 2.0 - 1.0;
+                                let tan = px.tangent(i);
+                                let tan = tan - norm * norm.dot(tan);
+                                let bit = tan.cross(norm) * bsign;
+                                let n = tan * nx + bit * ny + norm * nz;
+                                let m = n.length();
+                                if m > 1e-6 {
+                                    norm = n / m;
                                 }
-                                add_col.push(Color::RGB(buffer[ind], buffer[ind + 1], buffer[ind + 2]));
-                                let pot_col =
-                                    avg_cols(&add_col);
-                                
-                                if tex_w <= d_buf && tr_buf.0 > 0.0 {
-                                    tr_buf.1.scale(1.0 - tr_buf.0).add(pot_col.scale(tr_buf.0))
-                                } else if tex_w >= d_buf && tri_info.trs > 0.0 {
-                                    tr_buf
-                                        .1
-                                        .scale(tri_info.trs)
-                                        .add(pot_col.scale(1.0 - tri_info.trs))
-                                } else {
-                                    pot_col
+                            }
+                            let point = px.point(i);
+                            let view = (engine.camera.pos - point).normalize();
+                            let albedo = kd * px.col(i) * texel;
+                            //lights add up, ambient and emissive come on top
+                            let mut out = ambient * albedo + emissive;
+                            for light in &engine.lights {
+                                let (ldir, atten) = light.incident(point);
+                                let ldir = -ldir;
+                                let cos_theta = norm.dot(ldir);
+                                if atten <= 0.0 || cos_theta <= 0.0 {
+                                    continue;
+                                }
+                                let g = light.shadow(point, cos_theta, [x as f32, y as f32]);
+                                if g <= 0.0 {
+                                    continue;
                                 }
-                            } else {
-                                engine.ambient
-                            };
-
-                            if tex_w > d_buf {
-                                engine.depth_buffer[dbi] = tex_w;
-                                
-                                engine.transparency_buffer[dbi] =
-                                    (clamp(tri_info.trs*(1.0+tr_buf.0),0.0, 1.0), col);
+                                let f = cook_torrance(norm, view, ldir, albedo, metallic, roughness);
+                                //pi so a white lambert surface facing a light of intensity 1 comes out white
+                                let e = PI * light.intensity * atten * g * cos_theta;
+                                out += f * light.col * e;
                             }
-                            add_col.drain(1..);
-                            let buf_index = 3*(x+s.0*y) as usize;
-                            tex_buffer[buf_index] = col.r;
-                            tex_buffer[1+buf_index] = col.g;
-                            tex_buffer[2+buf_index] = col.b;
-                            //self.pixel(x.try_into().unwrap(), y.try_into().unwrap(), col).unwrap();
-
+                            out
+                        };
+
+                        if tri_info.trs > 0.0 {
+                            //translucent, accumulated and resolved after all of the tile is drawn
+                            let a = 1.0 - tri_info.trs;
+                            let w = a * oit_weight(1.0 / tex_w);
+                            //rgb weighted, a collects the weights
+                            self.accum[dbi] += Rgba::new(col.r * w, col.g * w, col.b * w, w);
+                            self.revealage[dbi] *= 1.0 - a;
+                        } else {
+                            if st.depth_write {
+                                self.depth[dbi] = z;
+                            }
+                            self.hdr[dbi] = col;
                         }
                     }
-                    
                 }
             }
         }
-        //println!("{:?}", start.elapsed());
     }
 }
+
+pub trait Surf{
+    fn color_at(&self, x:f32, y:f32)->Color;
+    fn apply_fn(&mut self, f:&dyn Fn(u32, u32, u32, u32, u32, Color)->Color);
+}
+
+impl Surf for Surface<'_>{
+    //bilinear between the four pixels around (x, y), pixel centres sit on whole coordinates
+    fn color_at(&self, x: f32, y: f32)->Color{
+        let buf = self.without_lock().unwrap();
+        let (w, h) = (self.width() as i32, self.height() as i32);
+        let bpp = self.pitch() as usize / w.max(1) as usize;
+        let pitch = self.pitch() as usize;
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = (x - x0, y - y0);
+        let px = |x: i32, y: i32| {
+            let i = bpp * clamp(x, 0, w - 1) as usize + pitch * clamp(y, 0, h - 1) as usize;
+            Rgba::rgb(buf[i] as f32, buf[i+1] as f32, buf[i+2] as f32)
+        };
+        let (x0, y0) = (x0 as i32, y0 as i32);
+        let c = px(x0, y0).lerp(px(x0+1, y0), fx).lerp(px(x0, y0+1).lerp(px(x0+1, y0+1), fx), fy);
+        Color::RGB((c.r+0.5) as u8, (c.g+0.5) as u8, (c.b+0.5) as u8)
+    }
+    fn apply_fn(&mut self, func:&dyn Fn(u32, u32, u32, u32, u32, Color)->Color){
+        let width = self.width();
+        let height = self.height();
+        let pitch = self.pitch();
+        let colb = self.without_lock_mut().unwrap();
+        for x in 0..width{
+            for y in 0..height{
+                let i = (x*(pitch/width) + y*pitch) as usize;
+                let color = func(x, y, width, height, pitch, Color::from((colb[i], colb[i+1], colb[i+2])));
+                colb[i] = color.r;
+                colb[i+1] = color.g;
+                colb[i+2] = color.b;
+            }
+        }
+    }
+
+}
@@ -0,0 +1,28 @@
+extern crate sdl2;
+
+pub mod color;
+pub mod depth;
+pub mod drawing;
+pub mod light;
+pub mod material;
+pub mod mesh;
+pub mod obj;
+pub mod ops;
+pub mod render;
+pub mod scene;
+pub mod simd;
+pub mod texture;
+pub mod world;
+
+pub use color::{Rgba, ToneMap};
+pub use depth::{DepthBuffer, DepthFunc};
+pub use light::{Light, LightKind, ShadowSettings};
+pub use material::{Cutout, Material};
+pub use mesh::{IndexedMesh, Submesh, Vertex};
+pub use obj::ObjError;
+pub use ops::{Mat4, Point3, Quat, Transform, Tri3d, Vec3, Vec4};
+pub use render::{FrameTarget, Renderer};
+pub use scene::{Instance, Node, NodeId, Scene};
+pub use simd::SimdLevel;
+pub use texture::{ColorSpace, Filter, Sampler, Texture, TextureManager, Wrap};
+pub use world::{Camera, Engine, Mesh};
@@ -1,63 +1,293 @@
-use crate::ops::{clamp, Tri3d, Vec3};
-use crate::world::{quick_inv, point_at};
-use sdl2::pixels::Color;
+use crate::ops::{clamp, Mat4, Point3, Tri3d, Vec3, Vec4};
+use crate::world::{clip_tri, Camera};
+use crate::color::Rgba;
+use crate::material::Cutout;
 use std::mem::swap;
 
 
-pub const SHADOW_RESOLUTION: (usize, usize) = (256, 256);
-pub const SPREAD_VAL: f32 = 1.0;
+//progressive: every prefix is spread over the whole disk, so any sample count can take the first n
+//the first tap is the centre, one sample is a plain lookup
+const POISSON_DISK: [[f32; 2]; 64] = [
+    [0.000_000, 0.000_000],
+    [-0.921_585, 0.336_432],
+    [0.819_554, 0.564_606],
+    [0.680_000, -0.725_731],
+    [-0.427_583, -0.902_046],
+    [-0.016_260, 0.913_279],
+    [-0.900_601, -0.399_187],
+    [0.646_122, -0.044_923],
+    [-0.567_371, 0.787_969],
+    [0.110_496, -0.636_361],
+    [0.260_020, 0.449_063],
+    [-0.489_685, -0.061_570],
+    [-0.304_051, 0.390_949],
+    [0.480_817, 0.846_453],
+    [-0.288_927, -0.492_719],
+    [0.371_746, -0.343_880],
+    [0.858_723, -0.381_817],
+    [-0.006_831, -0.990_146],
+    [-0.878_616, -0.018_809],
+    [0.934_535, 0.195_972],
+    [-0.715_699, -0.693_930],
+    [0.352_643, 0.125_443],
+    [0.361_500, -0.923_578],
+    [-0.029_911, 0.589_857],
+    [0.024_707, -0.327_128],
+    [-0.584_975, 0.246_283],
+    [0.554_090, 0.387_414],
+    [-0.313_698, 0.946_195],
+    [-0.609_433, -0.388_073],
+    [0.401_270, -0.636_518],
+    [0.931_081, -0.098_640],
+    [-0.785_407, 0.591_972],
+    [0.215_137, 0.744_446],
+    [-0.165_042, -0.755_766],
+    [-0.246_527, -0.204_742],
+    [-0.040_160, 0.287_659],
+    [-0.257_920, 0.065_237],
+    [-0.325_720, 0.662_724],
+    [0.253_720, -0.112_272],
+    [-0.539_216, 0.498_975],
+    [0.601_753, -0.432_560],
+    [-0.492_819, -0.612_532],
+    [0.465_439, 0.599_344],
+    [-0.750_071, -0.198_354],
+    [0.246_143, 0.955_444],
+    [0.675_747, 0.176_803],
+    [0.145_783, 0.174_603],
+    [0.160_616, -0.840_114],
+    [-0.072_066, -0.521_825],
+    [0.651_032, 0.694_759],
+    [-0.784_554, 0.169_433],
+    [-0.220_623, -0.957_336],
+    [0.799_824, 0.363_656],
+    [0.463_516, -0.143_709],
+    [0.696_694, -0.243_544],
+    [-0.432_049, -0.291_610],
+    [-0.975_463, -0.198_336],
+    [0.810_380, -0.571_263],
+    [0.228_345, -0.478_158],
+    [-0.139_746, 0.757_508],
+    [-0.733_907, 0.378_477],
+    [0.513_297, -0.810_076],
+    [-0.659_756, 0.026_147],
+    [-0.978_736, 0.144_242],
+];
 
-pub struct Light {
-    pub pos: [f32; 4],
-    pub col: Color,
-    pub dir: [f32; 4],
-    pub proj_mat: [[f32; 4]; 4],
-    pub look_mat : [[f32; 4]; 4],
-    pub buf: [f32; SHADOW_RESOLUTION.0*SHADOW_RESOLUTION.1],
+//near plane of the shadow projections, casters in front of it are clipped away
+pub const SHADOW_NEAR: f32 = 0.5;
+
+//+x, -x, +y, -y, +z, -z
+pub const CUBE_DIRS: [Vec3; 6] = [
+    Vec3::new(1.0, 0.0, 0.0),
+    Vec3::new(-1.0, 0.0, 0.0),
+    Vec3::new(0.0, 1.0, 0.0),
+    Vec3::new(0.0, -1.0, 0.0),
+    Vec3::new(0.0, 0.0, 1.0),
+    Vec3::new(0.0, 0.0, -1.0),
+];
+
+//how far past its slice a cascade reaches towards the sun, so tall casters outside the view still land in the map
+pub const CASCADE_PULLBACK: f32 = 50.0;
+//part of a cascade's half width over which it fades into the next one
+pub const CASCADE_BLEND: f32 = 0.1;
+//squared distance below which point and spot lights stop getting brighter, keeps surfaces at the light finite
+const MIN_DIST2: f32 = 1e-4;
+//0 splits the view evenly, 1 logarithmically
+const CASCADE_LAMBDA: f32 = 0.75;
+
+//index into CUBE_DIRS of the face `dir` points through
+pub fn cube_face(dir: Vec3) -> usize {
+    let (x, y, z) = (dir.x.abs(), dir.y.abs(), dir.z.abs());
+    if x >= y && x >= z {
+        if dir.x >= 0.0 { 0 } else { 1 }
+    } else if y >= z {
+        if dir.y >= 0.0 { 2 } else { 3 }
+    } else if dir.z >= 0.0 {
+        4
+    } else {
+        5
+    }
 }
 
-impl Light {
-    pub fn new(pos: [f32; 4], col: Color, dir: [f32; 4], proj_mat: [[f32; 4]; 4])  -> Self {
-        Light {
-            pos,
-            col,
-            dir,
+//view matrix looking from `pos` along `dir`, picks another up when looking straight up or down
+fn look_from(pos: Point3, dir: Vec3) -> Mat4 {
+    Mat4::point_at(pos, pos + dir, up_for(dir)).inverse_rigid()
+}
+
+fn up_for(dir: Vec3) -> Vec3 {
+    if dir.x.abs() < 1e-3 && dir.z.abs() < 1e-3 {
+        Vec3::Z
+    } else {
+        Vec3::Y
+    }
+}
+
+//cone angles are the full angle from the axis to the edge, in degrees like the camera fov
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum LightKind {
+    Directional,
+    Point { range: f32 },
+    Spot { range: f32, inner: f32, outer: f32 },
+    //directional light whose cascades cover the camera view out to `distance`
+    Sun { distance: f32 },
+}
+
+//tangent of the steepest angle the slope scaled bias accounts for
+const MAX_SLOPE: f32 = 10.0;
+
+//how a light renders and filters its shadow maps
+#[derive(Copy, Clone, Debug)]
+pub struct ShadowSettings {
+    //texels along each side of every map
+    pub resolution: usize,
+    //constant depth offset against acne
+    pub bias: f32,
+    //extra offset that grows as the surface turns away from the light
+    pub slope_bias: f32,
+    //filter radius in texels
+    pub radius: f32,
+    //taps per lookup, 1 to 64
+    pub samples: usize,
+    //turns the disk by a different angle every pixel, trades banding for noise
+    pub rotate: bool,
+}
+
+impl Default for ShadowSettings {
+    fn default() -> Self {
+        ShadowSettings {
+            resolution: 256,
+            bias: 0.005,
+            slope_bias: 0.0,
+            radius: 1.0,
+            samples: 16,
+            rotate: false,
+        }
+    }
+}
+
+//interleaved gradient noise, 0..1 and different for neighbouring pixels
+fn pixel_noise(pixel: [f32; 2]) -> f32 {
+    (52.982_918 * (0.067_110_56 * pixel[0] + 0.005_837_15 * pixel[1]).fract()).fract()
+}
+
+//one depth map rendered from the light, a point light has six of them
+pub struct ShadowMap {
+    pub size: usize,
+    pub proj_mat: Mat4,
+    pub look_mat: Mat4,
+    pub buf: Vec<f32>,
+}
+
+impl ShadowMap {
+    pub fn new(size: usize, proj_mat: Mat4, look_mat: Mat4) -> Self {
+        ShadowMap {
+            size,
             proj_mat,
-            look_mat : quick_inv(point_at(pos, pos.add(dir), [0.0, 1.0, 0.0, 1.0])),
-            buf: [1.0; SHADOW_RESOLUTION.0 * SHADOW_RESOLUTION.1],
+            look_mat,
+            buf: vec![1.0; size * size],
+        }
+    }
+    //also reallocates when the size changed
+    pub fn clear(&mut self, size: usize) {
+        if size != self.size {
+            self.size = size;
+            self.buf = vec![1.0; size * size];
+        } else {
+            self.buf.fill(1.0);
+        }
+    }
+    //x and y in -1..1 across the map and the depth `point` would have in it, None behind the light
+    pub fn project(&self, point: Point3) -> Option<[f32; 3]> {
+        let t = Vec4::from(point) * self.look_mat * self.proj_mat;
+        if t.w <= 0.0 {
+            return None;
+        }
+        let t3 = 1.0 / t.w;
+        Some([t.x * t3, t.y * t3, t.z * t3])
+    }
+    //fraction of the filter taps around `point` that are lit
+    //`cos` is the cosine between surface normal and light, `pixel` seeds the disk rotation
+    pub fn sample(&self, point: Point3, settings: &ShadowSettings, cos: f32, pixel: [f32; 2]) -> f32 {
+        match self.project(point) {
+            Some(p) => self.sample_projected(p, settings, cos, pixel),
+            None => 1.0,
+        }
+    }
+    fn sample_projected(&self, p: [f32; 3], settings: &ShadowSettings, cos: f32, pixel: [f32; 2]) -> f32 {
+        let cos = clamp(cos, 1e-3, 1.0);
+        let b = settings.bias + settings.slope_bias * ((1.0 - cos * cos).sqrt() / cos).min(MAX_SLOPE);
+        if p[2] < settings.bias {
+            return 0.0;
+        }
+        let size = self.size as f32;
+        let f0 = (p[0] + 1.0) * size * 0.5;
+        let f1 = (p[1] + 1.0) * size * 0.5;
+        let (sin_r, cos_r) = if settings.rotate {
+            (std::f32::consts::TAU * pixel_noise(pixel)).sin_cos()
+        } else {
+            (0.0, 1.0)
+        };
+        let taps = &POISSON_DISK[..clamp(settings.samples, 1, POISSON_DISK.len())];
+        let mut l = 0.0;
+        for item in taps {
+            let ox = (item[0] * cos_r - item[1] * sin_r) * settings.radius;
+            let oy = (item[0] * sin_r + item[1] * cos_r) * settings.radius;
+            //taps stay on the map, otherwise cube faces go dark along their edges
+            let x = clamp(f0 + ox, 0.0, size - 1.0) as usize;
+            let y = clamp(f1 + oy, 0.0, size - 1.0) as usize;
+            if p[2] - b <= self.buf[x + self.size * y] {
+                l += 1.0;
+            }
+        }
+        l / taps.len() as f32
+    }
+    //with a cutout, texels it discards let the light through
+    pub fn edit_shadow_buffer(&mut self, tri: Tri3d, cutout: Option<Cutout>) {
+        let view = tri.multiply_mat(self.look_mat);
+        //drop whatever is behind the light so it can not wrap around into the map
+        let out = &mut [Tri3d::empty(), Tri3d::empty()];
+        let n = clip_tri(Point3::new(0.0, 0.0, SHADOW_NEAR), Vec3::Z, view, out);
+        for t in out.iter().take(n) {
+            self.raster(t, cutout);
         }
     }
     #[inline]
-    pub fn edit_shadow_buffer(&mut self, tri: Tri3d) {
-        let rw = SHADOW_RESOLUTION.0 as f32 * 0.5;
-        let rh = SHADOW_RESOLUTION.1 as f32 * 0.5;
-        let t = tri
-            .multiply_mat(self.look_mat)
-            .multiply_mat(self.proj_mat);
-
-
-        let t03 = 1.0 / (t.ps[0][3] + 1.0);
-        let t13 = 1.0 / (t.ps[1][3] + 1.0);
-        let t23 = 1.0 / (t.ps[2][3] + 1.0);
-
-        let mut c1 = [
-            (t.ps[0][0] * t03 + 1.0) * rw,
-            (t.ps[0][1] * t03 + 1.0) * rh,
-            t.ps[0][2],
-            t03,
-        ];
-        let mut c2 = [
-            (t.ps[1][0] * t13 + 1.0) * rw,
-            (t.ps[1][1] * t13 + 1.0) * rh,
-            t.ps[1][2],
-            t13,
-        ];
-        let mut c3 = [
-            (t.ps[2][0] * t23 + 1.0) * rw,
-            (t.ps[2][1] * t23 + 1.0) * rh,
-            t.ps[2][2],
-            t23,
-        ];
+    fn raster(&mut self, t: &Tri3d, cutout: Option<Cutout>) {
+        let rw = self.size as f32 * 0.5;
+        let rh = self.size as f32 * 0.5;
+
+        //x, y, z/w, then u/w, v/w and 1/w for the cutout lookup
+        let corner = |i: usize| {
+            let c = Vec4::from(t.ps[i]) * self.proj_mat;
+            let iw = 1.0 / c.w;
+            [
+                (c.x * iw + 1.0) * rw,
+                (c.y * iw + 1.0) * rh,
+                c.z * iw,
+                t.uvs[i][0] * iw,
+                t.uvs[i][1] * iw,
+                iw,
+            ]
+        };
+        let mut c1 = corner(0);
+        let mut c2 = corner(1);
+        let mut c3 = corner(2);
+        //map gradients of u/w, v/w and 1/w for the cutout's mip level, as in the color pass
+        let area = (c2[0] - c1[0]) * (c3[1] - c1[1]) - (c3[0] - c1[0]) * (c2[1] - c1[1]);
+        let grad = |k: usize| -> (f32, f32) {
+            if area.abs() < 1e-12 {
+                return (0.0, 0.0);
+            }
+            let (a2, a3) = (c2[k] - c1[k], c3[k] - c1[k]);
+            (
+                (a2 * (c3[1] - c1[1]) - a3 * (c2[1] - c1[1])) / area,
+                (a3 * (c2[0] - c1[0]) - a2 * (c3[0] - c1[0])) / area,
+            )
+        };
+        let (ux, uy) = grad(3);
+        let (vx, vy) = grad(4);
+        let (wx, wy) = grad(5);
         if c1[1] > c2[1] {
             swap(&mut c1, &mut c2);
         }
@@ -70,15 +300,9 @@ impl Light {
             swap(&mut c2, &mut c3);
         }
 
-        let mut dax_step = 0.0;
-        let mut dbx_step = 0.0;
-        let mut dcx_step = 0.0;
-        let mut daz_step = 0.0;
-        let mut dbz_step = 0.0;
-        let mut dcz_step = 0.0;
-        let mut daw_step = 0.0;
-        let mut dbw_step = 0.0;
-        let mut dcw_step = 0.0;
+        let mut da_step = [0.0; 6];
+        let mut db_step = [0.0; 6];
+        let mut dc_step = [0.0; 6];
 
         let dya = (c2[1] - c1[1]).abs();
         let dyb = (c3[1] - c1[1]).abs();
@@ -87,63 +311,53 @@ impl Light {
         if dya != 0.0 {
             //point a to point b
             let da = 1.0 / dya;
-            dax_step = (c2[0] - c1[0]) * da;
-            daz_step = (c2[2] - c1[2]) * da;
-            daw_step = (c2[3] - c1[3]) * da;
+            da_step = std::array::from_fn(|k| (c2[k] - c1[k]) * da);
         }
 
         if dyb != 0.0 {
             //point a to point c
             let db = 1.0 / dyb;
-            dbx_step = (c3[0] - c1[0]) * db;
-            dbz_step = (c3[2] - c1[2]) * db;
-            dbw_step = (c3[3] - c1[3]) * db;
+            db_step = std::array::from_fn(|k| (c3[k] - c1[k]) * db);
         };
 
         if dyc != 0.0 {
             //point b to point c
             let dc = 1.0 / dyc;
-            dcx_step = (c3[0] - c2[0]) * dc;
-            dcz_step = (c3[2] - c2[2]) * dc;
-            dcw_step = (c3[3] - c2[3]) * dc;
+            dc_step = std::array::from_fn(|k| (c3[k] - c2[k]) * dc);
         }
+        let along = |c: &[f32; 6], step: &[f32; 6], ys: f32| -> [f32; 6] {
+            std::array::from_fn(|k| c[k] + ys * step[k])
+        };
         for y in c1[1] as i32 + 1..c3[1] as i32 + 1 {
-            if y > 0 && y < SHADOW_RESOLUTION.1 as i32 {
-                let mut ax: f32;
-
-                let mut az: f32;
-
-                let mut aw: f32;
+            if y >= 0 && y < self.size as i32 {
                 let ys1 = y as f32 - c1[1];
                 let ys2 = y as f32 - c2[1];
-                if y < c2[1] as i32 + 1 {
-                    ax = c1[0] + (ys1) * dax_step;
-
-                    az = c1[2] + (ys1) * daz_step;
-
-                    aw = c1[3] + (ys1) * daw_step;
+                let mut a = if y < c2[1] as i32 + 1 {
+                    along(&c1, &da_step, ys1)
                 } else {
-                    ax = c2[0] + (ys2) * dcx_step;
-                    
-                    az = c2[2] + (ys2) * dcz_step;
-                    
-                    aw = c2[3] + (ys2) * dcw_step;
+                    along(&c2, &dc_step, ys2)
+                };
+                let mut b = along(&c1, &db_step, ys1);
+                if a[0] > b[0] {
+                    swap(&mut a, &mut b);
                 }
-                let mut bx = c1[0] + (ys1) * dbx_step;
-                let mut bz = c1[2] + (ys1) * dbz_step;
-                let mut bw = c1[3] + (ys1) * dbw_step;
-                if ax > bx {
-                    swap(&mut ax, &mut bx);
-                    swap(&mut az, &mut bz);
-                    swap(&mut aw, &mut bw);
-                }
-                let tstep = 1.0 / (bx - ax);
-                for x in ax as usize..bx as usize {
-                    if x > 0 && x < SHADOW_RESOLUTION.0 {
-                        let t = (x as f32 - ax) * tstep;
-                        let z = ((1.0 - t) * az + t * bz) * ((1.0 - t) * aw + t * bw);
-                        let ind = x as usize + SHADOW_RESOLUTION.0 * y as usize;
+                let tstep = 1.0 / (b[0] - a[0]);
+                for x in a[0] as usize..b[0] as usize {
+                    if x < self.size {
+                        let t = (x as f32 - a[0]) * tstep;
+                        //z/w is affine in screen space so no perspective correction needed
+                        let z = (1.0 - t) * a[2] + t * b[2];
+                        let ind = x + self.size * y as usize;
                         if z < self.buf[ind] && z > 0.0 {
+                            if let Some(c) = cutout {
+                                let iw = 1.0 / ((1.0 - t) * a[5] + t * b[5]);
+                                let u = ((1.0 - t) * a[3] + t * b[3]) * iw;
+                                let v = ((1.0 - t) * a[4] + t * b[4]) * iw;
+                                let d = [(ux - u * wx) * iw, (vx - v * wx) * iw, (uy - u * wy) * iw, (vy - v * wy) * iw];
+                                if c.discards(u, v, d) {
+                                    continue;
+                                }
+                            }
                             self.buf[ind] = z;
                         }
                     }
@@ -152,3 +366,226 @@ impl Light {
         }
     }
 }
+
+pub struct Light {
+    pub kind: LightKind,
+    pub pos: Point3,
+    pub col: Rgba,
+    //scales col, 1 lights a white surface facing the light fully, at a distance of 1 for point and spot lights
+    pub intensity: f32,
+    //unit length, the constructors normalize it, keep it so when setting it directly
+    pub dir: Vec3,
+    //one map for directional and spot lights, the six cube faces in CUBE_DIRS order for point lights
+    pub maps: Vec<ShadowMap>,
+    //changes to the resolution take effect on the next shadow pass
+    pub settings: ShadowSettings,
+}
+
+impl Light {
+    pub fn new(pos: Point3, col: Rgba, dir: Vec3, proj_mat: Mat4)  -> Self {
+        let dir = dir.normalize();
+        Light {
+            kind: LightKind::Directional,
+            pos,
+            col,
+            intensity: 1.0,
+            dir,
+            maps: vec![ShadowMap::new(ShadowSettings::default().resolution, proj_mat, look_from(pos, dir))],
+            settings: ShadowSettings::default(),
+        }
+    }
+    pub fn point(pos: Point3, col: Rgba, range: f32) -> Self {
+        let proj_mat = Mat4::perspective(90.0, range, SHADOW_NEAR, 1.0, 1.0);
+        let settings = ShadowSettings::default();
+        Light {
+            kind: LightKind::Point { range },
+            pos,
+            col,
+            intensity: 1.0,
+            dir: -Vec3::Y,
+            maps: CUBE_DIRS.iter().map(|d| ShadowMap::new(settings.resolution, proj_mat, look_from(pos, *d))).collect(),
+            settings,
+        }
+    }
+    pub fn spot(pos: Point3, col: Rgba, dir: Vec3, range: f32, inner: f32, outer: f32) -> Self {
+        Light {
+            kind: LightKind::Spot { range, inner, outer },
+            ..Light::new(
+                pos,
+                col,
+                dir,
+                Mat4::perspective(2.0 * outer, range, SHADOW_NEAR, 1.0, 1.0),
+            )
+        }
+    }
+    //`cascades` is clamped to 2..=4, the maps are fitted to the camera every shadow pass
+    pub fn sun(col: Rgba, dir: Vec3, cascades: usize, distance: f32) -> Self {
+        let dir = dir.normalize();
+        let look_mat = look_from(Point3::ORIGIN, dir);
+        let settings = ShadowSettings::default();
+        Light {
+            kind: LightKind::Sun { distance },
+            pos: Point3::ORIGIN,
+            col,
+            intensity: 1.0,
+            dir,
+            maps: (0..clamp(cascades, 2, 4))
+                .map(|_| ShadowMap::new(settings.resolution, Mat4::ortho(1.0, 1.0, 0.0, 1.0), look_mat))
+                .collect(),
+            settings,
+        }
+    }
+    //direction the light travels to reach `point` and the fraction of it that gets there
+    #[inline]
+    pub fn incident(&self, point: Point3) -> (Vec3, f32) {
+        //inverse square, windowed so it still reaches 0 smoothly at the range
+        let falloff = |d: f32, range: f32| -> f32 {
+            let w = clamp(1.0 - (d / range).powi(2), 0.0, 1.0);
+            w * w / (d * d).max(MIN_DIST2)
+        };
+        match self.kind {
+            LightKind::Directional | LightKind::Sun { .. } => (self.dir, 1.0),
+            LightKind::Point { range } => {
+                let to = point - self.pos;
+                (to.normalize(), falloff(to.length(), range))
+            }
+            LightKind::Spot { range, inner, outer } => {
+                let to = point - self.pos;
+                let ldir = to.normalize();
+                let cos_in = inner.to_radians().cos();
+                let cos_out = outer.to_radians().cos();
+                let c = clamp((ldir.dot(self.dir) - cos_out) / (cos_in - cos_out).max(1e-4), 0.0, 1.0);
+                (ldir, c * c * (3.0 - 2.0 * c) * falloff(to.length(), range))
+            }
+        }
+    }
+    //how lit `point` is according to the shadow maps, point lights pick the cube face it falls in
+    //`cos` and `pixel` as in ShadowMap::sample
+    pub fn shadow(&self, point: Point3, cos: f32, pixel: [f32; 2]) -> f32 {
+        let s = &self.settings;
+        match self.kind {
+            LightKind::Point { .. } => self.maps[cube_face(point - self.pos)].sample(point, s, cos, pixel),
+            LightKind::Sun { .. } => self.cascade_shadow(point, cos, pixel),
+            _ => self.maps[0].sample(point, s, cos, pixel),
+        }
+    }
+    //first cascade that holds `point`, faded into the next one near its edge
+    fn cascade_shadow(&self, point: Point3, cos: f32, pixel: [f32; 2]) -> f32 {
+        let s = &self.settings;
+        for (i, map) in self.maps.iter().enumerate() {
+            let p = match map.project(point) {
+                Some(p) => p,
+                None => continue,
+            };
+            let edge = p[0].abs().max(p[1].abs());
+            if edge >= 1.0 || p[2] < 0.0 || p[2] > 1.0 {
+                continue;
+            }
+            let l = map.sample_projected(p, s, cos, pixel);
+            let k = (edge - (1.0 - CASCADE_BLEND)) / CASCADE_BLEND;
+            return match self.maps.get(i + 1) {
+                Some(next) if k > 0.0 => l + (next.sample(point, s, cos, pixel) - l) * k,
+                _ => l,
+            };
+        }
+        1.0
+    }
+    //call after moving or turning the light or the camera
+    //`aspect` is width over height of the target the camera renders into, what sun cascades are fitted to
+    pub fn update_views(&mut self, cam: &Camera, aspect: f32) {
+        match self.kind {
+            LightKind::Point { .. } => {
+                for (map, d) in self.maps.iter_mut().zip(CUBE_DIRS.iter()) {
+                    map.look_mat = look_from(self.pos, *d);
+                }
+            }
+            LightKind::Sun { distance } => self.fit_cascades(cam, aspect, distance),
+            _ => self.maps[0].look_mat = look_from(self.pos, self.dir),
+        }
+    }
+    //splits the view from the clip distance to `distance` into slices and wraps an ortho map around each
+    fn fit_cascades(&mut self, cam: &Camera, aspect: f32, distance: f32) {
+        let n = cam.clip_distance;
+        let f = distance.min(cam.render_distance);
+        let count = self.maps.len() as f32;
+        let t = (cam.fov.to_radians() * 0.5).tan();
+        //squared distance from the view axis of a slice corner, per unit of depth
+        let s = t * t * (1.0 + aspect * aspect);
+        let forward = cam.dir.normalize();
+        //rows are the light's right, up and forward
+        let basis = Mat4::point_at(Point3::ORIGIN, Point3::from_vec(self.dir), up_for(self.dir));
+        let (right, up, ahead) = (basis.transform_vector(Vec3::X), basis.transform_vector(Vec3::Y), basis.transform_vector(Vec3::Z));
+        let mut near = n;
+        for (i, map) in self.maps.iter_mut().enumerate() {
+            let k = (i + 1) as f32 / count;
+            let far = CASCADE_LAMBDA * n * (f / n).powf(k) + (1.0 - CASCADE_LAMBDA) * (n + (f - n) * k);
+            //smallest sphere around the slice, its centre sits on the view axis
+            let m = ((far + near) * (1.0 + s) * 0.5).min(far);
+            let r = (far * far * s + (far - m).powi(2))
+                .max(near * near * s + (m - near).powi(2))
+                .sqrt();
+            let c = (cam.pos + forward * m).to_vec();
+            //snap to whole texels so the shadow edges do not crawl as the camera moves
+            let texel = 2.0 * r / self.settings.resolution as f32;
+            let x = (c.dot(right) / texel).floor() * texel;
+            let y = (c.dot(up) / texel).floor() * texel;
+            let z = c.dot(ahead) - r - CASCADE_PULLBACK;
+            let eye = Point3::from_vec(right * x + up * y + ahead * z);
+            map.proj_mat = Mat4::ortho(r, r, 0.0, 2.0 * r + CASCADE_PULLBACK);
+            map.look_mat = look_from(eye, self.dir);
+            near = far;
+        }
+    }
+    pub fn clear_shadows(&mut self) {
+        for map in &mut self.maps {
+            map.clear(self.settings.resolution);
+        }
+    }
+    pub fn edit_shadow_buffer(&mut self, tri: Tri3d, cutout: Option<Cutout>) {
+        for map in &mut self.maps {
+            map.edit_shadow_buffer(tri, cutout);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inverse_square_falloff() {
+        let l = Light::point(Point3::ORIGIN, Rgba::WHITE, 100.0);
+        let at = |d: f32| l.incident(Point3::new(0.0, d, 0.0)).1;
+        //the window barely matters this far inside the range
+        assert!((at(1.0) - 1.0).abs() < 1e-3);
+        assert!((at(2.0) / at(1.0) - 0.25).abs() < 1e-3);
+        assert!((at(10.0) / at(1.0) - 0.01).abs() < 1e-3);
+        //and takes it to 0 at the range
+        assert!(at(99.0) < 1e-6 && at(100.0) == 0.0);
+        assert!(at(0.0).is_finite());
+    }
+
+    #[test]
+    fn cascades_follow_aspect() {
+        let cam = Camera {
+            fov: 90.0,
+            pos: Point3::ORIGIN,
+            dir: Vec3::Z,
+            vel: Vec3::ZERO,
+            rot_vel: Vec3::ZERO,
+            clip_distance: 0.5,
+            render_distance: 250.0,
+            //not what is rendered into, only the aspect passed in counts
+            window_height: 1.0,
+            window_width: 1.0,
+        };
+        //half width of every cascade's ortho projection
+        let radii = |aspect: f32| -> Vec<f32> {
+            let mut l = Light::sun(Rgba::WHITE, Vec3::new(0.3, -1.0, 0.1), 3, 80.0);
+            l.update_views(&cam, aspect);
+            l.maps.iter().map(|m| -1.0 / m.proj_mat.m[0][0]).collect()
+        };
+        let (square, wide) = (radii(1.0), radii(3.0));
+        assert!(square.iter().zip(&wide).all(|(s, w)| w > s), "{:?} {:?}", square, wide);
+    }
+}
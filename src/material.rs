@@ -0,0 +1,134 @@
+use crate::ops::clamp;
+use crate::color::Rgba;
+use crate::texture::{ColorSpace, Sampler, Texture, TextureManager};
+
+//what a map_d is tested against when nothing else sets alpha_cutoff
+pub const DEFAULT_ALPHA_CUTOFF: f32 = 0.5;
+
+//one `newmtl` block of a .mtl file, texture paths are already resolved against the .mtl location
+//kd is the albedo, metallic, roughness and emissive drive the cook-torrance shading
+#[derive(Clone, Debug)]
+pub struct Material {
+    pub name: String,
+    pub kd: Rgba,
+    pub ks: Rgba,
+    pub ns: f32,
+    pub d: f32,
+    pub metallic: f32,
+    pub roughness: f32,
+    pub emissive: Rgba,
+    //cutout, pixels whose map_d, or without one texture alpha, is below it are discarded
+    //a .mtl with map_d gets DEFAULT_ALPHA_CUTOFF
+    pub alpha_cutoff: Option<f32>,
+    pub map_kd: Option<String>,
+    pub map_bump: Option<String>,
+    pub map_d: Option<String>,
+    //scale roughness and metallic by their red channel and emissive by their color
+    pub map_pr: Option<String>,
+    pub map_pm: Option<String>,
+    pub map_ke: Option<String>,
+}
+
+impl Material {
+    pub fn new(name: String) -> Self {
+        Material {
+            name,
+            kd: Rgba::WHITE,
+            ks: Rgba::BLACK,
+            ns: 0.0,
+            d: 1.0,
+            metallic: 0.0,
+            roughness: 1.0,
+            emissive: Rgba::BLACK,
+            alpha_cutoff: None,
+            map_kd: None,
+            map_bump: None,
+            map_d: None,
+            map_pr: None,
+            map_pm: None,
+            map_ke: None,
+        }
+    }
+    //the alpha test to run with `color` as the texture drawn, none unless alpha_cutoff is set
+    pub fn cutout<'a>(&self, color: &'a Texture, textures: &'a TextureManager, sampler: Sampler) -> Option<Cutout<'a>> {
+        Some(Cutout {
+            threshold: self.alpha_cutoff?,
+            map_d: self.map_d.as_ref().and_then(|p| textures.get(p)),
+            color,
+            sampler,
+        })
+    }
+    //every texture path the material refers to, with what its texels hold
+    pub fn maps(&self) -> impl Iterator<Item = (&String, ColorSpace)> {
+        let maps = [
+            (&self.map_kd, ColorSpace::Srgb),
+            (&self.map_bump, ColorSpace::Linear),
+            (&self.map_d, ColorSpace::Linear),
+            (&self.map_pr, ColorSpace::Linear),
+            (&self.map_pm, ColorSpace::Linear),
+            (&self.map_ke, ColorSpace::Srgb),
+        ];
+        IntoIterator::into_iter(maps).filter_map(|(p, space)| Some((p.as_ref()?, space)))
+    }
+    //the single specular factor the rasterizer uses
+    pub fn rfl(&self) -> f32 {
+        (self.ks.r + self.ks.g + self.ks.b) / 3.0
+    }
+    pub fn trs(&self) -> f32 {
+        1.0 - self.d
+    }
+}
+
+//the alpha lookup of a cutout material, the color and shadow passes both test with it
+#[derive(Copy, Clone)]
+pub struct Cutout<'a> {
+    pub threshold: f32,
+    //read as grey, wins over the color texture's alpha
+    pub map_d: Option<&'a Texture>,
+    pub color: &'a Texture,
+    pub sampler: Sampler,
+}
+
+impl Cutout<'_> {
+    //`d` is how u and v change to the neighbouring pixels, as passed to Texture::lod
+    #[inline]
+    pub fn discards(&self, u: f32, v: f32, d: [f32; 4]) -> bool {
+        let sample = |t: &Texture| self.sampler.sample(t, u, v, t.lod(d[0], d[1], d[2], d[3]));
+        let alpha = match self.map_d {
+            Some(t) => sample(t).r,
+            None => sample(self.color).a,
+        };
+        alpha < self.threshold
+    }
+}
+
+//roughness for a phong exponent, used when a .mtl has `Ns` but no `Pr`
+pub fn ns_roughness(ns: f32) -> f32 {
+    (2.0 / (ns.max(0.0) + 2.0)).sqrt()
+}
+
+//roughness for the single reflectivity factor meshes are loaded with
+pub fn rfl_roughness(rfl: f32) -> f32 {
+    1.0 - 0.7 * clamp(rfl, 0.0, 1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cutout_prefers_map_d() {
+        let mut textures = TextureManager::new();
+        //opaque color, but a map_d that is black
+        textures.insert("kd", Texture::new(1, 1, 4, 4, vec![255; 4], ColorSpace::Srgb));
+        textures.insert("d", Texture::new(1, 1, 1, 1, vec![0], ColorSpace::Linear));
+        let color = textures.get("kd").unwrap_or_else(|| textures.white());
+        let mut m = Material::new("m".to_string());
+        assert!(m.cutout(color, &textures, Sampler::default()).is_none());
+        m.alpha_cutoff = Some(0.5);
+        let d = [0.0; 4];
+        assert!(!m.cutout(color, &textures, Sampler::default()).is_some_and(|c| c.discards(0.5, 0.5, d)));
+        m.map_d = Some("d".to_string());
+        assert!(m.cutout(color, &textures, Sampler::default()).is_some_and(|c| c.discards(0.5, 0.5, d)));
+    }
+}
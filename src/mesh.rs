@@ -0,0 +1,203 @@
+use crate::color::Rgba;
+use crate::ops::{Mat4, Point3, Tri3d, Vec3};
+use std::collections::HashMap;
+
+//one corner, shared by every triangle that uses it
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Vertex {
+    pub pos: Point3,
+    //same layout as Tri3d::uvs
+    pub uv: [f32; 3],
+    pub normal: Vec3,
+    pub tangent: Vec3,
+    pub bitangent: Vec3,
+    //multiplies the material's kd, white unless the mesh has vertex colors
+    pub col: Rgba,
+}
+
+impl Default for Vertex {
+    fn default() -> Self {
+        Vertex {
+            pos: Point3::ORIGIN,
+            uv: [0.0; 3],
+            normal: Vec3::ZERO,
+            tangent: Vec3::ZERO,
+            bitangent: Vec3::ZERO,
+            col: Rgba::WHITE,
+        }
+    }
+}
+
+impl Vertex {
+    pub fn translate(&self, t: Vec3) -> Self {
+        Vertex { pos: self.pos + t, ..*self }
+    }
+    //only the position, like Tri3d::scale
+    pub fn scale(&self, t: Vec3) -> Self {
+        Vertex { pos: Point3::from_vec(self.pos.to_vec() * t), ..*self }
+    }
+    //what Tri3d::transform does to each corner
+    pub fn transform(&self, m: Mat4, n: Mat4) -> Self {
+        let dir = |v: Vec3, m: &Mat4| m.transform_vector(v).normalize();
+        Vertex {
+            pos: m.transform_point(self.pos),
+            uv: self.uv,
+            normal: dir(self.normal, &n),
+            tangent: dir(self.tangent, &m),
+            bitangent: dir(self.bitangent, &m),
+            col: self.col,
+        }
+    }
+    //bit patterns, so only identical corners are merged
+    fn key(&self) -> [u32; 19] {
+        let (p, uv, n, t, b, c) = (self.pos, self.uv, self.normal, self.tangent, self.bitangent, self.col);
+        [p.x, p.y, p.z, uv[0], uv[1], uv[2], n.x, n.y, n.z, t.x, t.y, t.z, b.x, b.y, b.z, c.r, c.g, c.b, c.a].map(f32::to_bits)
+    }
+}
+
+//a run of triangles drawn with one material and the per triangle values Tri3d carries
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Submesh {
+    //range of IndexedMesh::indices, three per triangle
+    pub start: usize,
+    pub count: usize,
+    //index into Mesh::materials
+    pub mat: usize,
+    pub rfl: f32,
+    pub trs: f32,
+}
+
+//vertices shared between triangles, each vertex is transformed once and Tri3ds are only built for clipping
+#[derive(Clone, Debug, Default)]
+pub struct IndexedMesh {
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u32>,
+    pub submeshes: Vec<Submesh>,
+}
+
+impl IndexedMesh {
+    //converts a triangle soup, identical corners become one vertex
+    //neighbouring triangles with the same material, rfl and trs share a submesh, so the order is kept
+    pub fn from_tris(tris: &[Tri3d]) -> Self {
+        let mut mesh = IndexedMesh::default();
+        let mut seen: HashMap<[u32; 19], u32> = HashMap::new();
+        for t in tris {
+            let ids = [0, 1, 2].map(|j| {
+                let v = Vertex { pos: t.ps[j], uv: t.uvs[j], normal: t.ns[j], tangent: t.ts[j], bitangent: t.bs[j], col: t.cols[j] };
+                let vertices = &mut mesh.vertices;
+                *seen.entry(v.key()).or_insert_with(|| {
+                    vertices.push(v);
+                    (vertices.len() - 1) as u32
+                })
+            });
+            mesh.push(ids, t.mat, t.rfl, t.trs);
+        }
+        mesh
+    }
+    //appends a triangle of existing vertices, starting a submesh when mat, rfl or trs change
+    pub fn push(&mut self, ids: [u32; 3], mat: usize, rfl: f32, trs: f32) {
+        let start = self.indices.len();
+        match self.submeshes.last_mut() {
+            Some(s) if s.mat == mat && s.rfl == rfl && s.trs == trs => s.count += 3,
+            _ => self.submeshes.push(Submesh { start, count: 3, mat, rfl, trs }),
+        }
+        self.indices.extend_from_slice(&ids);
+    }
+    //number of triangles
+    pub fn len(&self) -> usize {
+        self.indices.len() / 3
+    }
+    pub fn is_empty(&self) -> bool {
+        self.indices.is_empty()
+    }
+    //the triangles in order with their corners looked up in vertices, a transformed copy of self.vertices
+    pub fn assemble<'a>(&'a self, vertices: &'a [Vertex]) -> impl Iterator<Item = Tri3d> + 'a {
+        self.assemble_indexed(vertices).map(|(_, t)| t)
+    }
+    //same as assemble, along with the index of every corner
+    pub fn assemble_indexed<'a>(&'a self, vertices: &'a [Vertex]) -> impl Iterator<Item = ([usize; 3], Tri3d)> + 'a {
+        self.submeshes.iter().flat_map(move |s| {
+            self.indices[s.start..s.start + s.count].chunks_exact(3).map(move |ids| {
+                let ids = [0, 1, 2].map(|j| ids[j] as usize);
+                let v = ids.map(|i| vertices[i]);
+                let tri = Tri3d {
+                    ps: v.map(|v| v.pos),
+                    uvs: v.map(|v| v.uv),
+                    ns: v.map(|v| v.normal),
+                    ts: v.map(|v| v.tangent),
+                    bs: v.map(|v| v.bitangent),
+                    cols: v.map(|v| v.col),
+                    rfl: s.rfl,
+                    trs: s.trs,
+                    mat: s.mat,
+                };
+                (ids, tri)
+            })
+        })
+    }
+    //back to a triangle soup
+    pub fn tris(&self) -> Vec<Tri3d> {
+        self.assemble(&self.vertices).collect()
+    }
+    //per vertex tangent frames from positions and uvs, summed over every triangle using the vertex
+    //the frame is made orthogonal to the vertex normal, vertices without one use their faces' normal
+    pub fn estimate_tangents(&mut self) {
+        let n = self.vertices.len();
+        let (mut tans, mut bits, mut faces) = (vec![Vec3::ZERO; n], vec![Vec3::ZERO; n], vec![Vec3::ZERO; n]);
+        for ids in self.indices.chunks_exact(3) {
+            let v = [0, 1, 2].map(|j| &self.vertices[ids[j] as usize]);
+            let e1 = v[1].pos - v[0].pos;
+            let e2 = v[2].pos - v[0].pos;
+            let face = e1.cross(e2);
+            let (du1, dv1) = (v[1].uv[0] - v[0].uv[0], v[1].uv[1] - v[0].uv[1]);
+            let (du2, dv2) = (v[2].uv[0] - v[0].uv[0], v[2].uv[1] - v[0].uv[1]);
+            let det = du1 * dv2 - du2 * dv1;
+            let r = if det.abs() < 1e-12 { 0.0 } else { 1.0 / det };
+            let tan = e1 * (dv2 * r) - e2 * (dv1 * r);
+            let bit = e2 * (du1 * r) - e1 * (du2 * r);
+            for &i in ids {
+                let i = i as usize;
+                tans[i] += tan;
+                bits[i] += bit;
+                faces[i] += face;
+            }
+        }
+        let unit = |v: Vec3| -> Option<Vec3> {
+            let m = v.length();
+            if m > 1e-12 { Some(v / m) } else { None }
+        };
+        for (i, v) in self.vertices.iter_mut().enumerate() {
+            let n = unit(v.normal).or_else(|| unit(faces[i])).unwrap_or(Vec3::Z);
+            let (tan, bit) = (tans[i], bits[i]);
+            //gram-schmidt, any perpendicular will do when the uvs gave nothing
+            let tan = unit(tan - n * n.dot(tan))
+                .or_else(|| unit(Vec3::new(n.y, -n.x, 0.0)))
+                .unwrap_or(Vec3::X);
+            v.bitangent = unit(bit - n * n.dot(bit) - tan * tan.dot(bit)).unwrap_or_else(|| tan.cross(n));
+            v.tangent = tan;
+        }
+    }
+    //smooth normals for the vertices that have none, from the faces around them weighted by area
+    pub fn fill_normals(&mut self) {
+        let mut sums = vec![Vec3::ZERO; self.vertices.len()];
+        for ids in self.indices.chunks_exact(3) {
+            let p = [0, 1, 2].map(|j| self.vertices[ids[j] as usize].pos);
+            let face = (p[1] - p[0]).cross(p[2] - p[0]);
+            for &i in ids {
+                sums[i as usize] += face;
+            }
+        }
+        for (v, n) in self.vertices.iter_mut().zip(sums) {
+            if v.normal == Vec3::ZERO {
+                v.normal = n.normalize();
+            }
+        }
+    }
+    pub fn map_vertices(&self, f: impl Fn(&Vertex) -> Vertex) -> Self {
+        IndexedMesh {
+            vertices: self.vertices.iter().map(f).collect(),
+            indices: self.indices.clone(),
+            submeshes: self.submeshes.clone(),
+        }
+    }
+}
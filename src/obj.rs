@@ -0,0 +1,496 @@
+use crate::color::Rgba;
+use crate::material::{ns_roughness, rfl_roughness, Material, DEFAULT_ALPHA_CUTOFF};
+use crate::mesh::{IndexedMesh, Vertex};
+use crate::ops::{Point3, Vec3};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug)]
+pub enum ObjErrorKind {
+    Io(io::Error),
+    BadNumber,
+    BadIndex,
+    MissingValue,
+    Unsupported,
+    UnknownMaterial,
+}
+
+//line is 0 when the file could not be opened at all
+#[derive(Debug)]
+pub struct ObjError {
+    pub path: PathBuf,
+    pub line: usize,
+    pub token: String,
+    pub kind: ObjErrorKind,
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let what = match &self.kind {
+            ObjErrorKind::Io(e) => return write!(f, "{}:{}: {}", self.path.display(), self.line, e),
+            ObjErrorKind::BadNumber => "bad number",
+            ObjErrorKind::BadIndex => "bad index",
+            ObjErrorKind::MissingValue => "missing value after",
+            ObjErrorKind::Unsupported => "unsupported statement",
+            ObjErrorKind::UnknownMaterial => "unknown material",
+        };
+        write!(f, "{}:{}: {} `{}`", self.path.display(), self.line, what, self.token)
+    }
+}
+
+impl Error for ObjError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match &self.kind {
+            ObjErrorKind::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+//statements we accept but have no use for yet: grouping, display attributes,
+//points and lines, and free-form curves and surfaces, none of which make triangles
+const IGNORED: [&str; 27] = [
+    "o", "g", "s", "mg", "l", "p", "vp", "cstype", "deg", "bmat", "step", "curv", "curv2", "surf",
+    "parm", "trim", "hole", "scrv", "sp", "end", "con", "bevel", "c_interp", "d_interp", "lod",
+    "shadow_obj", "trace_obj",
+];
+
+struct Parser<'a> {
+    path: &'a Path,
+    line: usize,
+}
+
+impl Parser<'_> {
+    fn err(&self, token: &str, kind: ObjErrorKind) -> ObjError {
+        ObjError {
+            path: self.path.to_path_buf(),
+            line: self.line,
+            token: token.to_string(),
+            kind,
+        }
+    }
+    fn float(&self, vals: &[&str], i: usize) -> Result<f32, ObjError> {
+        let tok = vals.get(i).ok_or_else(|| self.err(vals[0], ObjErrorKind::MissingValue))?;
+        tok.parse::<f32>().map_err(|_| self.err(tok, ObjErrorKind::BadNumber))
+    }
+    //linear, above 1 is allowed for emissive colors
+    fn color(&self, vals: &[&str]) -> Result<Rgba, ObjError> {
+        let c = |i| -> Result<f32, ObjError> { Ok(self.float(vals, i)?.max(0.0)) };
+        Ok(Rgba::rgb(c(1)?, c(2)?, c(3)?))
+    }
+    //map statements may carry options before the file name, e.g. `map_Bump -bm 1.0 normal.png`
+    fn map(&self, vals: &[&str]) -> Result<String, ObjError> {
+        if vals.len() < 2 {
+            return Err(self.err(vals[0], ObjErrorKind::MissingValue));
+        }
+        let dir = self.path.parent().unwrap_or_else(|| Path::new(""));
+        Ok(dir.join(vals[vals.len() - 1]).to_string_lossy().into_owned())
+    }
+    //obj indices start at 1, negative ones count back from the latest element
+    fn index(&self, tok: &str, len: usize) -> Result<usize, ObjError> {
+        match tok.parse::<isize>() {
+            Ok(i) if i >= 1 && i as usize <= len => Ok(i as usize - 1),
+            Ok(i) if i < 0 && i.unsigned_abs() <= len => Ok(len - i.unsigned_abs()),
+            _ => Err(self.err(tok, ObjErrorKind::BadIndex)),
+        }
+    }
+    //v, v/vt, v//vn or v/vt/vn
+    fn face_vert(&self, tok: &str, lens: [usize; 3]) -> Result<FaceVert, ObjError> {
+        let parts: Vec<&str> = tok.split('/').collect();
+        if parts.len() > 3 {
+            return Err(self.err(tok, ObjErrorKind::BadIndex));
+        }
+        let opt = |i: usize, len: usize| -> Result<Option<usize>, ObjError> {
+            match parts.get(i) {
+                Some(t) if !t.is_empty() => self.index(t, len).map(Some),
+                Some(_) if i == 1 && parts.len() == 3 => Ok(None),
+                Some(_) => Err(self.err(tok, ObjErrorKind::BadIndex)),
+                None => Ok(None),
+            }
+        };
+        Ok(FaceVert {
+            v: self.index(parts[0], lens[0])?,
+            vt: opt(1, lens[1])?,
+            vn: opt(2, lens[2])?,
+        })
+    }
+}
+
+struct FaceVert {
+    v: usize,
+    vt: Option<usize>,
+    vn: Option<usize>,
+}
+
+//ear clipping on the polygon projected along its dominant axis, falls back to a fan if it gets stuck
+fn triangulate(ps: &[Point3]) -> Vec<[usize; 3]> {
+    let n = ps.len();
+    if n == 3 {
+        return vec![[0, 1, 2]];
+    }
+    //newell normal
+    let mut norm = Vec3::ZERO;
+    for i in 0..n {
+        let a = ps[i];
+        let b = ps[(i + 1) % n];
+        norm.x += (a.y - b.y) * (a.z + b.z);
+        norm.y += (a.z - b.z) * (a.x + b.x);
+        norm.z += (a.x - b.x) * (a.y + b.y);
+    }
+    let (u, v) = if norm.x.abs() >= norm.y.abs() && norm.x.abs() >= norm.z.abs() {
+        (1, 2)
+    } else if norm.y.abs() >= norm.z.abs() {
+        (2, 0)
+    } else {
+        (0, 1)
+    };
+    let flat: Vec<[f32; 2]> = ps.iter().map(|p| [p.to_array()[u], p.to_array()[v]]).collect();
+    let cross = |a: [f32; 2], b: [f32; 2], c: [f32; 2]| (b[0] - a[0]) * (c[1] - a[1]) - (b[1] - a[1]) * (c[0] - a[0]);
+    let mut area = 0.0;
+    for i in 0..n {
+        area += cross([0.0, 0.0], flat[i], flat[(i + 1) % n]);
+    }
+    let sign = if area < 0.0 { -1.0 } else { 1.0 };
+
+    let mut left: Vec<usize> = (0..n).collect();
+    let mut out = Vec::with_capacity(n - 2);
+    while left.len() > 3 {
+        let m = left.len();
+        let ear = (0..m).find(|&i| {
+            let (a, b, c) = (left[(i + m - 1) % m], left[i], left[(i + 1) % m]);
+            if cross(flat[a], flat[b], flat[c]) * sign <= 0.0 {
+                return false;
+            }
+            !left.iter().any(|&o| {
+                o != a && o != b && o != c
+                    && cross(flat[a], flat[b], flat[o]) * sign >= 0.0
+                    && cross(flat[b], flat[c], flat[o]) * sign >= 0.0
+                    && cross(flat[c], flat[a], flat[o]) * sign >= 0.0
+            })
+        });
+        match ear {
+            Some(i) => {
+                out.push([left[(i + m - 1) % m], left[i], left[(i + 1) % m]]);
+                left.remove(i);
+            }
+            None => {
+                for i in 1..m - 1 {
+                    out.push([left[0], left[i], left[i + 1]]);
+                }
+                return out;
+            }
+        }
+    }
+    out.push([left[0], left[1], left[2]]);
+    out
+}
+
+pub fn load_mtl(path: &Path) -> Result<Vec<Material>, ObjError> {
+    let p = Parser { path, line: 0 };
+    let file = File::open(path).map_err(|e| p.err("", ObjErrorKind::Io(e)))?;
+    parse_mtl(BufReader::new(file), path)
+}
+
+//path is only used for errors and to find the maps next to it
+fn parse_mtl(reader: impl BufRead, path: &Path) -> Result<Vec<Material>, ObjError> {
+    let mut p = Parser { path, line: 0 };
+    let mut mats: Vec<Material> = Vec::new();
+    //an explicit `Pr` wins over the roughness guessed from `Ns`
+    let mut has_pr = false;
+
+    for line in reader.lines() {
+        p.line += 1;
+        let line = line.map_err(|e| p.err("", ObjErrorKind::Io(e)))?;
+        let vals: Vec<&str> = line.split_whitespace().collect();
+        if vals.is_empty() || vals[0].starts_with('#') {
+            continue;
+        }
+        if vals[0] == "newmtl" {
+            let name = vals.get(1).ok_or_else(|| p.err(vals[0], ObjErrorKind::MissingValue))?;
+            mats.push(Material::new(name.to_string()));
+            has_pr = false;
+            continue;
+        }
+        let m = match mats.last_mut() {
+            Some(m) => m,
+            None => return Err(p.err(vals[0], ObjErrorKind::UnknownMaterial)),
+        };
+        match vals[0] {
+            "Kd" => m.kd = p.color(&vals)?,
+            "Ks" => m.ks = p.color(&vals)?,
+            "Ns" => {
+                m.ns = p.float(&vals, 1)?;
+                if !has_pr {
+                    m.roughness = ns_roughness(m.ns);
+                }
+            }
+            "Pr" => {
+                m.roughness = p.float(&vals, 1)?;
+                has_pr = true;
+            }
+            "Pm" => m.metallic = p.float(&vals, 1)?,
+            "Ke" => m.emissive = p.color(&vals)?,
+            "d" => m.d = p.float(&vals, 1)?,
+            "Tr" => m.d = 1.0 - p.float(&vals, 1)?,
+            "map_Kd" => m.map_kd = Some(p.map(&vals)?),
+            "map_Bump" | "map_bump" | "bump" => m.map_bump = Some(p.map(&vals)?),
+            //mtl has no key for the threshold, a map_d is only useful as a cutout
+            "map_d" => {
+                m.map_d = Some(p.map(&vals)?);
+                m.alpha_cutoff.get_or_insert(DEFAULT_ALPHA_CUTOFF);
+            }
+            "map_Pr" => m.map_pr = Some(p.map(&vals)?),
+            "map_Pm" => m.map_pm = Some(p.map(&vals)?),
+            "map_Ke" => m.map_ke = Some(p.map(&vals)?),
+            //exporters write plenty of keys we have no use for, e.g. Ka, illum, sheen (Ps),
+            //clearcoat (Pc, Pcr), anisotropy or displacement maps, so anything else is skipped
+            _ => {}
+        }
+    }
+    Ok(mats)
+}
+
+//material 0 is built from the passed in col, rfl and trs and is used until the first `usemtl`
+//every distinct v/vt/vn triple of the faces becomes one vertex, vertex colors go on the vertices
+pub fn load_obj(path: &Path, col: Rgba, rfl: f32, trs: f32) -> Result<(IndexedMesh, Vec<Material>), ObjError> {
+    let p = Parser { path, line: 0 };
+    let file = File::open(path).map_err(|e| p.err("", ObjErrorKind::Io(e)))?;
+    parse_obj(BufReader::new(file), path, col, rfl, trs)
+}
+
+//path is only used for errors and to find mtllibs next to it
+fn parse_obj(reader: impl BufRead, path: &Path, col: Rgba, rfl: f32, trs: f32) -> Result<(IndexedMesh, Vec<Material>), ObjError> {
+    let mut p = Parser { path, line: 0 };
+    let mut mesh = IndexedMesh::default();
+    let mut seen: HashMap<(usize, Option<usize>, Option<usize>), u32> = HashMap::new();
+    let mut t_n: Vec<Vec3> = Vec::new();
+    let mut points: Vec<Point3> = Vec::new();
+    let mut t_c: Vec<[f32; 3]> = Vec::new();
+    let mut v_cols: Vec<Option<Rgba>> = Vec::new();
+    let mut mats = vec![Material {
+        kd: col,
+        ks: Rgba::grey(rfl),
+        d: 1.0 - trs,
+        roughness: rfl_roughness(rfl),
+        ..Material::new(String::new())
+    }];
+    let mut cur_mat = 0;
+
+    for line in reader.lines() {
+        p.line += 1;
+        let line = line.map_err(|e| p.err("", ObjErrorKind::Io(e)))?;
+        let vals: Vec<&str> = line.split_whitespace().collect();
+        if vals.is_empty() || vals[0].starts_with('#') {
+            continue;
+        }
+        match vals[0] {
+            "v" => {
+                points.push(Point3::new(p.float(&vals, 1)?, p.float(&vals, 2)?, p.float(&vals, 3)?));
+                //optional trailing r g b in 0..1
+                v_cols.push(if vals.len() >= 7 {
+                    let c = vals.len() - 3;
+                    Some(Rgba::rgb(p.float(&vals, c)?, p.float(&vals, c + 1)?, p.float(&vals, c + 2)?))
+                } else {
+                    None
+                });
+            }
+            //v and w are optional, w is not used
+            "vt" => {
+                let v = if vals.len() > 2 { p.float(&vals, 2)? } else { 0.0 };
+                t_c.push([1.0 - p.float(&vals, 1)?, 1.0 - v, 1.0]);
+            }
+            "vn" => t_n.push(Vec3::new(p.float(&vals, 1)?, p.float(&vals, 2)?, p.float(&vals, 3)?).normalize()),
+            "f" => {
+                if vals.len() < 4 {
+                    return Err(p.err(vals[0], ObjErrorKind::MissingValue));
+                }
+                let lens = [points.len(), t_c.len(), t_n.len()];
+                let verts = vals[1..].iter().map(|v| p.face_vert(v, lens)).collect::<Result<Vec<_>, _>>()?;
+                let poly: Vec<Point3> = verts.iter().map(|fv| points[fv.v]).collect();
+                let vertices = &mut mesh.vertices;
+                let ids: Vec<u32> = verts
+                    .iter()
+                    .map(|fv| {
+                        *seen.entry((fv.v, fv.vt, fv.vn)).or_insert_with(|| {
+                            //one uv for every corner without a vt, missing normals are filled in at the end
+                            vertices.push(Vertex {
+                                pos: points[fv.v],
+                                uv: fv.vt.map_or([0.0, 0.0, 1.0], |i| t_c[i]),
+                                normal: fv.vn.map_or(Vec3::ZERO, |i| t_n[i]),
+                                col: v_cols[fv.v].unwrap_or(Rgba::WHITE),
+                                ..Vertex::default()
+                            });
+                            (vertices.len() - 1) as u32
+                        })
+                    })
+                    .collect();
+                let (mrfl, mtrs) = if cur_mat == 0 { (rfl, trs) } else { (mats[cur_mat].rfl(), mats[cur_mat].trs()) };
+                for [a, b, c] in triangulate(&poly) {
+                    mesh.push([ids[a], ids[b], ids[c]], cur_mat, mrfl, mtrs);
+                }
+            }
+            "mtllib" => {
+                let dir = path.parent().unwrap_or_else(|| Path::new(""));
+                for lib in &vals[1..] {
+                    mats.extend(load_mtl(&dir.join(lib))?);
+                }
+            }
+            "usemtl" => {
+                let name = vals.get(1).ok_or_else(|| p.err(vals[0], ObjErrorKind::MissingValue))?;
+                cur_mat = mats
+                    .iter()
+                    .skip(1)
+                    .position(|m| m.name == *name)
+                    .map(|i| i + 1)
+                    .ok_or_else(|| p.err(name, ObjErrorKind::UnknownMaterial))?;
+            }
+            s if IGNORED.contains(&s) => {}
+            s => return Err(p.err(s, ObjErrorKind::Unsupported)),
+        }
+    }
+    mesh.fill_normals();
+    mesh.estimate_tangents();
+    Ok((mesh, mats))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ops::Tri3d;
+    use crate::texture::ColorSpace;
+
+    fn parse(src: &str) -> Result<Vec<Tri3d>, ObjError> {
+        parse_obj(src.as_bytes(), Path::new("test.obj"), Rgba::WHITE, 0.0, 0.0).map(|(mesh, _)| mesh.tris())
+    }
+
+    fn fails(src: &str) -> (ObjErrorKind, usize, String) {
+        match parse(src) {
+            Err(e) => (e.kind, e.line, e.token),
+            Ok(_) => panic!("parsed {:?}", src),
+        }
+    }
+
+    #[test]
+    fn missing_file() {
+        let e = match load_obj(Path::new("no/such/file.obj"), Rgba::WHITE, 0.0, 0.0) {
+            Err(e) => e,
+            Ok(_) => panic!("opened a missing file"),
+        };
+        assert!(matches!(e.kind, ObjErrorKind::Io(_)));
+        assert_eq!(e.line, 0);
+    }
+
+    #[test]
+    fn bad_number() {
+        let (kind, line, token) = fails("v 0 0 0\nv 1 x 2\n");
+        assert!(matches!(kind, ObjErrorKind::BadNumber));
+        assert_eq!((line, token.as_str()), (2, "x"));
+    }
+
+    #[test]
+    fn bad_index() {
+        let (kind, line, token) = fails("v 0 0 0\nv 1 0 0\nv 0 1 0\n\nf 1 2 9\n");
+        assert!(matches!(kind, ObjErrorKind::BadIndex));
+        assert_eq!((line, token.as_str()), (5, "9"));
+    }
+
+    #[test]
+    fn unknown_material() {
+        let (kind, line, token) = fails("# no mtllib\nusemtl nope\n");
+        assert!(matches!(kind, ObjErrorKind::UnknownMaterial));
+        assert_eq!((line, token.as_str()), (2, "nope"));
+    }
+
+    const SQUARE: &str = "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nvn 0 0 1\nvt 0.25\n";
+
+    #[test]
+    fn quad() {
+        let ts = parse(&format!("{}f 1 2 3 4\n", SQUARE)).unwrap_or_else(|e| panic!("{}", e));
+        assert_eq!(ts.len(), 2);
+        let area: f32 = ts.iter().map(|t| 0.5 * (t.ps[1] - t.ps[0]).cross(t.ps[2] - t.ps[0]).length()).sum();
+        assert!((area - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn ngon() {
+        let src = "v 0 0 0\nv 4 0 0\nv 4 4 0\nv 2 1 0\nv 0 4 0\nf 1 2 3 4 5\n";
+        let ts = parse(src).unwrap_or_else(|e| panic!("{}", e));
+        assert_eq!(ts.len(), 3);
+        //concave at vertex 4, a fan from vertex 1 would cover 14 by reaching outside the polygon
+        let area: f32 = ts.iter().map(|t| 0.5 * (t.ps[1] - t.ps[0]).cross(t.ps[2] - t.ps[0]).length()).sum();
+        assert!((area - 10.0).abs() < 1e-5, "{}", area);
+    }
+
+    #[test]
+    fn negative_indices() {
+        let a = parse(&format!("{}f 1 2 3\n", SQUARE)).unwrap_or_else(|e| panic!("{}", e));
+        let b = parse(&format!("{}f -4 -3 -2\n", SQUARE)).unwrap_or_else(|e| panic!("{}", e));
+        assert_eq!(a[0].ps, b[0].ps);
+        let (kind, _, token) = fails(&format!("{}f -5 1 2\n", SQUARE));
+        assert!(matches!(kind, ObjErrorKind::BadIndex));
+        assert_eq!(token, "-5");
+    }
+
+    #[test]
+    fn vertex_normal_only() {
+        let ts = parse(&format!("{}f 1//1 2//1 3//1\n", SQUARE)).unwrap_or_else(|e| panic!("{}", e));
+        assert_eq!(ts[0].ns, [Vec3::new(0.0, 0.0, 1.0); 3]);
+        //no vt given, so the one default uv and not the file's
+        assert_eq!(ts[0].uvs, [[0.0, 0.0, 1.0]; 3]);
+    }
+
+    #[test]
+    fn one_texture_coordinate() {
+        let ts = parse(&format!("{}f 1/1 2/1 3/1\n", SQUARE)).unwrap_or_else(|e| panic!("{}", e));
+        assert_eq!(ts[0].uvs[0], [0.75, 1.0, 1.0]);
+    }
+
+    #[test]
+    fn shared_corners() {
+        //two triangles of a quad share the corners with the same v/vt/vn, the vt on 3 splits it
+        let src = format!("{}vt 0.5\nf 1 2 3\nf 1 3 4\nf 1/1 2/1 3/2\n", SQUARE);
+        let (mesh, _) = parse_obj(src.as_bytes(), Path::new("test.obj"), Rgba::WHITE, 0.0, 0.0).unwrap_or_else(|e| panic!("{}", e));
+        assert_eq!(mesh.indices, [0, 1, 2, 0, 2, 3, 4, 5, 6]);
+        assert_eq!(mesh.vertices.len(), 7);
+        //without vn the normal comes from the faces, +z for this winding
+        assert!(mesh.vertices.iter().all(|v| v.normal == Vec3::Z), "{:?}", mesh.vertices);
+    }
+
+    #[test]
+    fn vertex_colors() {
+        let src = "v 0 0 0 1 0 0\nv 1 0 0 0 1 0\nv 0 1 0\nf 1 2 3\n";
+        let ts = parse(src).unwrap_or_else(|e| panic!("{}", e));
+        assert_eq!(ts[0].cols, [Rgba::RED, Rgba::GREEN, Rgba::WHITE]);
+    }
+
+    #[test]
+    fn ignored_statements() {
+        let src = format!("{}o a\ng b\ns off\nl 1 2\np 3\nvp 0.5\ncstype bspline\ndeg 3\nf 1 2 3\n", SQUARE);
+        assert_eq!(parse(&src).unwrap_or_else(|e| panic!("{}", e)).len(), 1);
+    }
+
+    #[test]
+    fn pbr_material() {
+        let src = "newmtl m\nNs 10\nPr 0.25\nPm 0.5\nKe 1 2 3\nmap_Pr r.png\nmap_Pm m.png\nmap_Ke e.png\nmap_d d.png\n\
+                   Ps 1\nPc 0.5\nPcr 0.1\naniso 0.2\nanisor 0.3\nnorm n.png\ndisp h.png\nillum 2\nvendor_key 1\n";
+        let mats = parse_mtl(src.as_bytes(), Path::new("dir/test.mtl")).unwrap_or_else(|e| panic!("{}", e));
+        let m = &mats[0];
+        assert_eq!((m.roughness, m.metallic, m.emissive), (0.25, 0.5, Rgba::rgb(1.0, 2.0, 3.0)));
+        assert_eq!(m.alpha_cutoff, Some(DEFAULT_ALPHA_CUTOFF));
+        let maps: Vec<(&String, ColorSpace)> = m.maps().collect();
+        let (lin, srgb) = (ColorSpace::Linear, ColorSpace::Srgb);
+        let want = [("dir/d.png", lin), ("dir/r.png", lin), ("dir/m.png", lin), ("dir/e.png", srgb)];
+        assert!(maps.iter().map(|(p, s)| (p.as_str(), *s)).eq(want.iter().copied()), "{:?}", maps);
+    }
+
+    #[test]
+    fn unknown_keyword() {
+        let (kind, line, token) = fails("v 0 0 0\nfoo 1 2\n");
+        assert!(matches!(kind, ObjErrorKind::Unsupported));
+        assert_eq!((line, token.as_str()), (2, "foo"));
+    }
+}
@@ -1,109 +1,509 @@
-use crate::Engine;
-use sdl2::pixels::Color;
+use crate::color::Rgba;
 use std::cmp::PartialOrd;
+use std::ops::{Add, AddAssign, Div, Mul, MulAssign, Neg, Sub, SubAssign};
 
-#[inline]
-fn fisqrt(x: f32)-> f32{
-    if x == 0.0{
-        return 0.0
+//a direction or offset, translations do not move it
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Vec3 {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+}
+
+//a position, a point minus a point is a Vec3
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Point3 {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+}
+
+//homogeneous coordinates, what a projection turns a point into
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Vec4 {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+    pub w: f32,
+}
+
+//for row vectors, v * a * b applies a first, translation sits in the last row
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Mat4 {
+    pub m: [[f32; 4]; 4],
+}
+
+impl Vec3 {
+    pub const ZERO: Vec3 = Vec3 { x: 0.0, y: 0.0, z: 0.0 };
+    pub const X: Vec3 = Vec3 { x: 1.0, y: 0.0, z: 0.0 };
+    pub const Y: Vec3 = Vec3 { x: 0.0, y: 1.0, z: 0.0 };
+    pub const Z: Vec3 = Vec3 { x: 0.0, y: 0.0, z: 1.0 };
+
+    #[inline]
+    pub const fn new(x: f32, y: f32, z: f32) -> Self {
+        Vec3 { x, y, z }
+    }
+    #[inline]
+    pub fn dot(self, v: Vec3) -> f32 {
+        self.x * v.x + self.y * v.y + self.z * v.z
+    }
+    //right handed, X.cross(Y) == Z
+    #[inline]
+    pub fn cross(self, v: Vec3) -> Vec3 {
+        Vec3 {
+            x: self.y * v.z - self.z * v.y,
+            y: self.z * v.x - self.x * v.z,
+            z: self.x * v.y - self.y * v.x,
+        }
     }
-    let x2: f32 = x * 0.5f32;
-    let mut i: u32 = unsafe { std::mem::transmute(x) }; // evil floating point bit level hacking
-    i = 0x5f375a86 - (i >> 1);                        // what the fuck?
-    let y: f32 = unsafe { std::mem::transmute(i) };
-    let y  = y * ( 1.5 - ( x2 * y * y ) );     // 1st iteration
-    //let y  = y * ( 1.5 - ( x2 * y * y ) );       // 2nd iteration, this can be removed
+    #[inline]
+    pub fn length(self) -> f32 {
+        self.dot(self).sqrt()
+    }
+    //the zero vector stays zero
+    #[inline]
+    pub fn normalize(self) -> Vec3 {
+        let l = self.length();
+        if l > 0.0 { self / l } else { self }
+    }
+    #[inline]
+    pub fn lerp(self, v: Vec3, t: f32) -> Vec3 {
+        self + (v - self) * t
+    }
+    #[inline]
+    pub fn to_array(self) -> [f32; 3] {
+        [self.x, self.y, self.z]
+    }
+}
 
-    return y;
+impl Point3 {
+    pub const ORIGIN: Point3 = Point3 { x: 0.0, y: 0.0, z: 0.0 };
+
+    #[inline]
+    pub const fn new(x: f32, y: f32, z: f32) -> Self {
+        Point3 { x, y, z }
+    }
+    //offset from the origin
+    #[inline]
+    pub fn to_vec(self) -> Vec3 {
+        Vec3::new(self.x, self.y, self.z)
+    }
+    #[inline]
+    pub fn from_vec(v: Vec3) -> Self {
+        Point3::new(v.x, v.y, v.z)
+    }
+    #[inline]
+    pub fn lerp(self, p: Point3, t: f32) -> Point3 {
+        self + (p - self) * t
+    }
+    #[inline]
+    pub fn to_array(self) -> [f32; 3] {
+        [self.x, self.y, self.z]
+    }
 }
 
-pub trait Vec3 {
-    fn scale(&self, scalar: [f32; 4]) -> Self;
-    fn scale_c(&self, scalar: f32) -> Self;
-    fn add(&self, a: [f32; 4]) -> Self;
-    fn subtract(&self, a: [f32; 4]) -> Self;
-    fn magnitude(&self) -> f32;
-    fn normalize(&self) -> Self;
-    fn negative(&self) -> Self;
-    fn cross_product(&self, c: [f32; 4]) -> Self;
-    fn dot_product(&self, d: [f32; 4]) -> f32;
-    fn multiply_mat(&self, mat: [[f32; 4]; 4]) -> Self;
+impl Vec4 {
+    #[inline]
+    pub const fn new(x: f32, y: f32, z: f32, w: f32) -> Self {
+        Vec4 { x, y, z, w }
+    }
+    #[inline]
+    pub fn dot(self, v: Vec4) -> f32 {
+        self.x * v.x + self.y * v.y + self.z * v.z + self.w * v.w
+    }
+    #[inline]
+    pub fn xyz(self) -> Vec3 {
+        Vec3::new(self.x, self.y, self.z)
+    }
+    //divides by w, the caller makes sure it is not 0
+    #[inline]
+    pub fn to_point(self) -> Point3 {
+        let w = 1.0 / self.w;
+        Point3::new(self.x * w, self.y * w, self.z * w)
+    }
 }
-impl Vec3 for [f32; 4] {
+
+//w 1
+impl From<Point3> for Vec4 {
     #[inline]
-    fn scale(&self, scalar: [f32; 4]) -> [f32; 4] {
-        [
-            self[0] * scalar[0],
-            self[1] * scalar[1],
-            self[2] * scalar[2],
-            self[3],
-        ]
+    fn from(p: Point3) -> Self {
+        Vec4::new(p.x, p.y, p.z, 1.0)
     }
+}
+
+//w 0
+impl From<Vec3> for Vec4 {
     #[inline]
-    fn scale_c(&self, scalar: f32) -> Self {
-        [self[0] * scalar, self[1] * scalar, self[2] * scalar, self[3]]
+    fn from(v: Vec3) -> Self {
+        Vec4::new(v.x, v.y, v.z, 0.0)
     }
+}
+
+impl Add for Vec3 {
+    type Output = Vec3;
     #[inline]
-    fn add(&self, a: [f32; 4]) -> [f32; 4] {
-        [self[0] + a[0], self[1] + a[1], self[2] + a[2], self[3]]
+    fn add(self, v: Vec3) -> Vec3 {
+        Vec3::new(self.x + v.x, self.y + v.y, self.z + v.z)
     }
+}
+
+impl AddAssign for Vec3 {
     #[inline]
-    fn subtract(&self, a: [f32; 4]) -> [f32; 4] {
-        [self[0] - a[0], self[1] - a[1], self[2] - a[2], self[3]]
+    fn add_assign(&mut self, v: Vec3) {
+        *self = *self + v;
     }
+}
+
+impl Sub for Vec3 {
+    type Output = Vec3;
     #[inline]
-    fn magnitude(&self) -> f32 {
-        (self[0]*self[0] + self[1]*self[1] + self[2]*self[2]).sqrt()
+    fn sub(self, v: Vec3) -> Vec3 {
+        Vec3::new(self.x - v.x, self.y - v.y, self.z - v.z)
     }
+}
+
+impl SubAssign for Vec3 {
     #[inline]
-    fn normalize(&self) -> [f32; 4] {
-        let m = fisqrt(self[0]*self[0]+self[1]*self[1]+self[2]*self[2]);
-        [self[0] * m, self[1] * m, self[2] * m, self[3]]
+    fn sub_assign(&mut self, v: Vec3) {
+        *self = *self - v;
     }
-    
-    fn negative(&self) -> [f32; 4] {
-        [-self[0], -self[1], -self[2], 1.0]
+}
+
+impl Neg for Vec3 {
+    type Output = Vec3;
+    #[inline]
+    fn neg(self) -> Vec3 {
+        Vec3::new(-self.x, -self.y, -self.z)
     }
+}
+
+impl Mul<f32> for Vec3 {
+    type Output = Vec3;
     #[inline]
-    fn cross_product(&self, c: [f32; 4]) -> [f32; 4] {
-        [
-            -self[1] * c[2] + c[1] * self[2],
-            -self[2] * c[0] + c[2] * self[0],
-            -self[0] * c[1] + c[0] * self[1],
-            1.0,
-        ]
+    fn mul(self, s: f32) -> Vec3 {
+        Vec3::new(self.x * s, self.y * s, self.z * s)
     }
+}
+
+impl MulAssign<f32> for Vec3 {
     #[inline]
-    fn dot_product(&self, d: Self) -> f32 {
-        self[0] * d[0] + self[1] * d[1] + self[2] * d[2]
+    fn mul_assign(&mut self, s: f32) {
+        *self = *self * s;
     }
+}
+
+//per component
+impl Mul for Vec3 {
+    type Output = Vec3;
     #[inline]
-    fn multiply_mat(&self, m: [[f32; 4]; 4]) -> [f32; 4] {
-        [
-            self[0] * m[0][0] + self[1] * m[1][0] + self[2] * m[2][0] + self[3] * m[3][0],
-            self[0] * m[0][1] + self[1] * m[1][1] + self[2] * m[2][1] + self[3] * m[3][1],
-            self[0] * m[0][2] + self[1] * m[1][2] + self[2] * m[2][2] + self[3] * m[3][2],
-            self[0] * m[0][3] + self[1] * m[1][3] + self[2] * m[2][3] + self[3] * m[3][3],
-        ]
+    fn mul(self, v: Vec3) -> Vec3 {
+        Vec3::new(self.x * v.x, self.y * v.y, self.z * v.z)
     }
+}
 
+impl Div<f32> for Vec3 {
+    type Output = Vec3;
+    #[inline]
+    fn div(self, s: f32) -> Vec3 {
+        self * (1.0 / s)
+    }
+}
+
+impl Add<Vec3> for Point3 {
+    type Output = Point3;
+    #[inline]
+    fn add(self, v: Vec3) -> Point3 {
+        Point3::new(self.x + v.x, self.y + v.y, self.z + v.z)
+    }
+}
+
+impl AddAssign<Vec3> for Point3 {
+    #[inline]
+    fn add_assign(&mut self, v: Vec3) {
+        *self = *self + v;
+    }
+}
+
+impl Sub<Vec3> for Point3 {
+    type Output = Point3;
+    #[inline]
+    fn sub(self, v: Vec3) -> Point3 {
+        Point3::new(self.x - v.x, self.y - v.y, self.z - v.z)
+    }
+}
+
+impl Sub for Point3 {
+    type Output = Vec3;
+    #[inline]
+    fn sub(self, p: Point3) -> Vec3 {
+        Vec3::new(self.x - p.x, self.y - p.y, self.z - p.z)
+    }
+}
+
+impl Add for Vec4 {
+    type Output = Vec4;
+    #[inline]
+    fn add(self, v: Vec4) -> Vec4 {
+        Vec4::new(self.x + v.x, self.y + v.y, self.z + v.z, self.w + v.w)
+    }
+}
+
+impl Sub for Vec4 {
+    type Output = Vec4;
+    #[inline]
+    fn sub(self, v: Vec4) -> Vec4 {
+        Vec4::new(self.x - v.x, self.y - v.y, self.z - v.z, self.w - v.w)
+    }
+}
+
+impl Mul<f32> for Vec4 {
+    type Output = Vec4;
+    #[inline]
+    fn mul(self, s: f32) -> Vec4 {
+        Vec4::new(self.x * s, self.y * s, self.z * s, self.w * s)
+    }
+}
+
+impl Mul<Mat4> for Vec4 {
+    type Output = Vec4;
+    #[inline]
+    fn mul(self, m: Mat4) -> Vec4 {
+        let m = &m.m;
+        Vec4::new(
+            self.x * m[0][0] + self.y * m[1][0] + self.z * m[2][0] + self.w * m[3][0],
+            self.x * m[0][1] + self.y * m[1][1] + self.z * m[2][1] + self.w * m[3][1],
+            self.x * m[0][2] + self.y * m[1][2] + self.z * m[2][2] + self.w * m[3][2],
+            self.x * m[0][3] + self.y * m[1][3] + self.z * m[2][3] + self.w * m[3][3],
+        )
+    }
+}
+
+//a then b
+impl Mul for Mat4 {
+    type Output = Mat4;
+    fn mul(self, b: Mat4) -> Mat4 {
+        let mut m = [[0.0; 4]; 4];
+        for (row, a) in m.iter_mut().zip(&self.m) {
+            for (j, v) in row.iter_mut().enumerate() {
+                *v = a[0] * b.m[0][j] + a[1] * b.m[1][j] + a[2] * b.m[2][j] + a[3] * b.m[3][j];
+            }
+        }
+        Mat4 { m }
+    }
+}
+
+impl Default for Mat4 {
+    fn default() -> Self {
+        Mat4::IDENTITY
+    }
+}
+
+impl Mat4 {
+    pub const IDENTITY: Mat4 = Mat4 {
+        m: [
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ],
+    };
+
+    #[inline]
+    pub const fn new(m: [[f32; 4]; 4]) -> Self {
+        Mat4 { m }
+    }
+    pub fn translation(v: Vec3) -> Self {
+        let mut m = Mat4::IDENTITY;
+        m.m[3] = [v.x, v.y, v.z, 1.0];
+        m
+    }
+    pub fn scaling(s: Vec3) -> Self {
+        Mat4::new([
+            [s.x, 0.0, 0.0, 0.0],
+            [0.0, s.y, 0.0, 0.0],
+            [0.0, 0.0, s.z, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+    pub fn rotation_x(angle: f32) -> Self {
+        Mat4::new([
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, angle.cos(), angle.sin(), 0.0],
+            [0.0, -angle.sin(), angle.cos(), 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+    pub fn rotation_y(angle: f32) -> Self {
+        Mat4::new([
+            [angle.cos(), 0.0, -angle.sin(), 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [angle.sin(), 0.0, angle.cos(), 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+    //clockwise looking down z, unlike the other two
+    pub fn rotation_z(angle: f32) -> Self {
+        Mat4::new([
+            [angle.cos(), -angle.sin(), 0.0, 0.0],
+            [angle.sin(), angle.cos(), 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+    //view space has the camera at the origin looking down +z, x and y come out flipped for the screen
+    pub fn perspective(fov: f32, render_distance: f32, clip_distance: f32, window_width: f32, window_height: f32) -> Self {
+        let t = (fov.to_radians() * 0.5).tan();
+        let zratio = render_distance / (render_distance - clip_distance);
+        Mat4::new([
+            [-window_height / (t * window_width), 0.0, 0.0, 0.0],
+            [0.0, -1.0 / t, 0.0, 0.0],
+            [0.0, 0.0, zratio, 1.0],
+            [0.0, 0.0, -clip_distance * zratio, 0.0],
+        ])
+    }
+    //z from n to f goes to 0 to 1, flipped like perspective
+    pub fn ortho(r: f32, t: f32, n: f32, f: f32) -> Self {
+        Mat4::new([
+            [-1.0 / r, 0.0, 0.0, 0.0],
+            [0.0, -1.0 / t, 0.0, 0.0],
+            [0.0, 0.0, 1.0 / (f - n), 0.0],
+            [0.0, 0.0, -n / (f - n), 1.0],
+        ])
+    }
+    //places something at `pos` facing `target`, rows are its right, up, forward and position
+    //the inverse is a view matrix
+    pub fn point_at(pos: Point3, target: Point3, up: Vec3) -> Self {
+        let forward = (target - pos).normalize();
+        let up = (up - forward * up.dot(forward)).normalize();
+        let right = forward.cross(up);
+        Mat4::new([
+            [right.x, right.y, right.z, 0.0],
+            [up.x, up.y, up.z, 0.0],
+            [forward.x, forward.y, forward.z, 0.0],
+            [pos.x, pos.y, pos.z, 1.0],
+        ])
+    }
+    //rotation and translation only, much cheaper than inverse
+    pub fn inverse_rigid(&self) -> Self {
+        let m = &self.m;
+        Mat4::new([
+            [m[0][0], m[1][0], m[2][0], 0.0],
+            [m[0][1], m[1][1], m[2][1], 0.0],
+            [m[0][2], m[1][2], m[2][2], 0.0],
+            [
+                -(m[3][0] * m[0][0] + m[3][1] * m[0][1] + m[3][2] * m[0][2]),
+                -(m[3][0] * m[1][0] + m[3][1] * m[1][1] + m[3][2] * m[1][2]),
+                -(m[3][0] * m[2][0] + m[3][1] * m[2][1] + m[3][2] * m[2][2]),
+                1.0,
+            ],
+        ])
+    }
+    pub fn transpose(&self) -> Self {
+        let mut m = [[0.0; 4]; 4];
+        for (i, row) in m.iter_mut().enumerate() {
+            for (j, v) in row.iter_mut().enumerate() {
+                *v = self.m[j][i];
+            }
+        }
+        Mat4 { m }
+    }
+    //2x2 determinants of the top two rows and of the bottom two, shared by determinant and inverse
+    #[inline]
+    fn minors(&self) -> ([f32; 6], [f32; 6]) {
+        let a = &self.m;
+        (
+            [
+                a[0][0] * a[1][1] - a[1][0] * a[0][1],
+                a[0][0] * a[1][2] - a[1][0] * a[0][2],
+                a[0][0] * a[1][3] - a[1][0] * a[0][3],
+                a[0][1] * a[1][2] - a[1][1] * a[0][2],
+                a[0][1] * a[1][3] - a[1][1] * a[0][3],
+                a[0][2] * a[1][3] - a[1][2] * a[0][3],
+            ],
+            [
+                a[2][0] * a[3][1] - a[3][0] * a[2][1],
+                a[2][0] * a[3][2] - a[3][0] * a[2][2],
+                a[2][0] * a[3][3] - a[3][0] * a[2][3],
+                a[2][1] * a[3][2] - a[3][1] * a[2][2],
+                a[2][1] * a[3][3] - a[3][1] * a[2][3],
+                a[2][2] * a[3][3] - a[3][2] * a[2][3],
+            ],
+        )
+    }
+    pub fn determinant(&self) -> f32 {
+        let (s, c) = self.minors();
+        s[0] * c[5] - s[1] * c[4] + s[2] * c[3] + s[3] * c[2] - s[4] * c[1] + s[5] * c[0]
+    }
+    //None when the matrix is singular
+    pub fn inverse(&self) -> Option<Self> {
+        let a = &self.m;
+        let (s, c) = self.minors();
+        let det = self.determinant();
+        if det.abs() < f32::MIN_POSITIVE {
+            return None;
+        }
+        let d = 1.0 / det;
+        Some(Mat4::new([
+            [
+                (a[1][1] * c[5] - a[1][2] * c[4] + a[1][3] * c[3]) * d,
+                (-a[0][1] * c[5] + a[0][2] * c[4] - a[0][3] * c[3]) * d,
+                (a[3][1] * s[5] - a[3][2] * s[4] + a[3][3] * s[3]) * d,
+                (-a[2][1] * s[5] + a[2][2] * s[4] - a[2][3] * s[3]) * d,
+            ],
+            [
+                (-a[1][0] * c[5] + a[1][2] * c[2] - a[1][3] * c[1]) * d,
+                (a[0][0] * c[5] - a[0][2] * c[2] + a[0][3] * c[1]) * d,
+                (-a[3][0] * s[5] + a[3][2] * s[2] - a[3][3] * s[1]) * d,
+                (a[2][0] * s[5] - a[2][2] * s[2] + a[2][3] * s[1]) * d,
+            ],
+            [
+                (a[1][0] * c[4] - a[1][1] * c[2] + a[1][3] * c[0]) * d,
+                (-a[0][0] * c[4] + a[0][1] * c[2] - a[0][3] * c[0]) * d,
+                (a[3][0] * s[4] - a[3][1] * s[2] + a[3][3] * s[0]) * d,
+                (-a[2][0] * s[4] + a[2][1] * s[2] - a[2][3] * s[0]) * d,
+            ],
+            [
+                (-a[1][0] * c[3] + a[1][1] * c[1] - a[1][2] * c[0]) * d,
+                (a[0][0] * c[3] - a[0][1] * c[1] + a[0][2] * c[0]) * d,
+                (-a[3][0] * s[3] + a[3][1] * s[1] - a[3][2] * s[0]) * d,
+                (a[2][0] * s[3] - a[2][1] * s[1] + a[2][2] * s[0]) * d,
+            ],
+        ]))
+    }
+    //w is taken as 1 and the result's w dropped, right for anything but a projection
+    #[inline]
+    pub fn transform_point(&self, p: Point3) -> Point3 {
+        let v = Vec4::from(p) * *self;
+        Point3::new(v.x, v.y, v.z)
+    }
+    //w is taken as 0, translation does not apply
+    #[inline]
+    pub fn transform_vector(&self, v: Vec3) -> Vec3 {
+        (Vec4::from(v) * *self).xyz()
+    }
 }
 
 #[derive(Copy,Clone)]
 pub struct Tri3d {
-    pub ps: [[f32; 4]; 3],
+    pub ps: [Point3; 3],
     pub uvs: [[f32; 3]; 3],
-    pub ns: [[f32; 4]; 3],
-    pub col: Color,
+    pub ns: [Vec3; 3],
+    //per vertex tangent and bitangent, along +u and +v of the stored uvs
+    pub ts: [Vec3; 3],
+    pub bs: [Vec3; 3],
+    //vertex colors
+    pub cols: [Rgba; 3],
     pub rfl: f32,
     pub trs: f32,
+    pub mat: usize,
 }
 impl Tri3d {
+    //col goes on every corner
     pub fn new(
-        ps: [[f32; 4]; 3],
+        ps: [Point3; 3],
         uvs: [[f32; 3]; 3],
-        ns: [[f32; 4]; 3],
-        col: Color,
+        ns: [Vec3; 3],
+        col: Rgba,
         rfl: f32,
         trs: f32,
     ) -> Self {
@@ -111,91 +511,243 @@ impl Tri3d {
             ps,
             uvs,
             ns,
-            col,
+            ts: [Vec3::ZERO; 3],
+            bs: [Vec3::ZERO; 3],
+            cols: [col; 3],
             rfl,
             trs,
+            mat: 0,
         }
     }
     pub fn empty() -> Self {
         Tri3d {
-            ps: [
-                [0.0, 0.0, 0.0, 1.0],
-                [0.0, 0.0, 0.0, 1.0],
-                [0.0, 0.0, 0.0, 1.0],
-            ],
+            ps: [Point3::ORIGIN; 3],
             uvs: [[0.0, 0.0, 0.0], [0.0, 0.0, 0.0], [0.0, 0.0, 0.0]],
-            ns: [
-                [0.0, 0.0, 0.0, 1.0],
-                [0.0, 0.0, 0.0, 1.0],
-                [0.0, 0.0, 0.0, 1.0],
-            ],
-            col: Color::WHITE,
+            ns: [Vec3::ZERO; 3],
+            ts: [Vec3::ZERO; 3],
+            bs: [Vec3::ZERO; 3],
+            cols: [Rgba::WHITE; 3],
             rfl: 0.0,
             trs: 0.0,
+            mat: 0,
         }
     }
-    pub fn normal(&self) -> [f32; 4] {
-        self.ps[2]
-            .subtract(self.ps[0])
-            .cross_product(self.ps[1].subtract(self.ps[0]))
+    pub fn normal(&self) -> Vec3 {
+        (self.ps[1] - self.ps[0])
+            .cross(self.ps[2] - self.ps[0])
             .normalize() //sheeeesh
     }
-    pub fn translate(&self, t: [f32; 4]) -> Self {
-        Self::new(
-            [self.ps[0].add(t), self.ps[1].add(t), self.ps[2].add(t)],
-            self.uvs,
-            self.ns,
-            self.col,
-            self.rfl,
-            self.trs,
-        )
+    pub fn translate(&self, t: Vec3) -> Self {
+        Self {
+            ps: [self.ps[0] + t, self.ps[1] + t, self.ps[2] + t],
+            ..*self
+        }
     }
-    pub fn scale(&self, t: [f32; 4]) -> Self {
-        Self::new(
-            [
-                self.ps[0].scale(t),
-                self.ps[1].scale(t),
-                self.ps[2].scale(t),
-            ],
-            self.uvs,
-            self.ns,
-            self.col,
-            self.rfl,
-            self.trs,
-        )
+    pub fn scale(&self, t: Vec3) -> Self {
+        let s = |p: Point3| Point3::from_vec(p.to_vec() * t);
+        Self {
+            ps: [s(self.ps[0]), s(self.ps[1]), s(self.ps[2])],
+            ..*self
+        }
     }
-    pub fn center(&self) -> [f32; 4] {
-        self.ps[0]
-            .add(self.ps[1])
-            .add(self.ps[2])
-            .scale([0.333, 0.333, 0.333, 0.333])
+    pub fn center(&self) -> Point3 {
+        Point3::from_vec((self.ps[0].to_vec() + self.ps[1].to_vec() + self.ps[2].to_vec()) / 3.0)
     }
+    //points and directions alike, only right for rotations and translations
     #[inline]
-    pub fn multiply_mat(&self, m: [[f32; 4]; 4]) -> Self {
+    pub fn multiply_mat(&self, m: Mat4) -> Self {
+        self.transform(m, m)
+    }
+    //points by m, normals by its inverse transpose n, tangents by m without the translation
+    //directions come out unit length
+    pub fn transform(&self, m: Mat4, n: Mat4) -> Self {
+        let dir = |v: Vec3, m: &Mat4| m.transform_vector(v).normalize();
         Self {
-            ps: [
-                self.ps[0].multiply_mat(m),
-                self.ps[1].multiply_mat(m),
-                self.ps[2].multiply_mat(m),
-            ],
-            uvs: self.uvs,
-            ns: [
-                self.ns[0].multiply_mat(m),
-                self.ns[1].multiply_mat(m),
-                self.ns[2].multiply_mat(m),
-            ],
-            col: self.col,
-            rfl: self.rfl,
-            trs: self.trs,
+            ps: [m.transform_point(self.ps[0]), m.transform_point(self.ps[1]), m.transform_point(self.ps[2])],
+            ns: [dir(self.ns[0], &n), dir(self.ns[1], &n), dir(self.ns[2], &n)],
+            ts: [dir(self.ts[0], &m), dir(self.ts[1], &m), dir(self.ts[2], &m)],
+            bs: [dir(self.bs[0], &m), dir(self.bs[1], &m), dir(self.bs[2], &m)],
+            ..*self
         }
     }
     pub fn upd(
         &self,
-        trans: [f32; 4],
-        rot: [f32; 4],
-        rot_point: [f32; 4],
+        trans: Vec3,
+        rot: Vec3,
+        rot_point: Point3,
     ) -> Self {
-        self.translate(rot_point.negative()).multiply_mat(Engine::z_rot(rot[2])).multiply_mat(Engine::y_rot(rot[1])).multiply_mat(Engine::x_rot(rot[0])).translate(rot_point).translate(trans)
+        let m = Quat::from_euler(rot.x, rot.y, rot.z).to_matrix();
+        self.translate(-rot_point.to_vec()).multiply_mat(m).translate(rot_point.to_vec()).translate(trans)
+    }
+}
+
+//unit quaternion, a rotation without euler's gimbal lock that interpolates smoothly
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Quat {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+    pub w: f32,
+}
+
+impl Quat {
+    pub const IDENTITY: Quat = Quat { x: 0.0, y: 0.0, z: 0.0, w: 1.0 };
+
+    //counter clockwise looking down the axis, the axis does not need to be unit length
+    pub fn from_axis_angle(axis: Vec3, angle: f32) -> Self {
+        let m = axis.length();
+        if m == 0.0 {
+            return Quat::IDENTITY;
+        }
+        let (s, c) = (angle * 0.5).sin_cos();
+        let s = s / m;
+        Quat { x: axis.x * s, y: axis.y * s, z: axis.z * s, w: c }
+    }
+    //same as Mat4::rotation_z, then rotation_y, then rotation_x, z included turns the other way round
+    pub fn from_euler(x: f32, y: f32, z: f32) -> Self {
+        Quat::from_axis_angle(Vec3::X, x) * Quat::from_axis_angle(Vec3::Y, y) * Quat::from_axis_angle(-Vec3::Z, z)
+    }
+    #[inline]
+    pub fn dot(self, q: Quat) -> f32 {
+        self.x * q.x + self.y * q.y + self.z * q.z + self.w * q.w
+    }
+    //the inverse of a unit quaternion
+    #[inline]
+    pub fn conjugate(self) -> Self {
+        Quat { x: -self.x, y: -self.y, z: -self.z, w: self.w }
+    }
+    pub fn normalize(self) -> Self {
+        let m = self.dot(self).sqrt();
+        if m == 0.0 {
+            return Quat::IDENTITY;
+        }
+        Quat { x: self.x / m, y: self.y / m, z: self.z / m, w: self.w / m }
+    }
+    #[inline]
+    pub fn rotate(self, v: Vec3) -> Vec3 {
+        //v + 2w(q x v) + 2q x (q x v)
+        let q = Vec3::new(self.x, self.y, self.z);
+        let t = q.cross(v) * 2.0;
+        v + t * self.w + q.cross(t)
+    }
+    //shortest way round, t 0 is self and 1 is q
+    pub fn slerp(self, q: Quat, t: f32) -> Self {
+        let mut d = self.dot(q);
+        //q and -q are the same rotation, take the one on this side
+        let q = if d < 0.0 {
+            d = -d;
+            Quat { x: -q.x, y: -q.y, z: -q.z, w: -q.w }
+        } else {
+            q
+        };
+        let (a, b) = if d > 0.9995 {
+            //nearly the same, lerp avoids dividing by sin of almost 0
+            (1.0 - t, t)
+        } else {
+            let theta = d.acos();
+            let s = theta.sin();
+            (((1.0 - t) * theta).sin() / s, (t * theta).sin() / s)
+        };
+        Quat {
+            x: a * self.x + b * q.x,
+            y: a * self.y + b * q.y,
+            z: a * self.z + b * q.z,
+            w: a * self.w + b * q.w,
+        }
+        .normalize()
+    }
+    //q.to_matrix().transform_vector(v) == q.rotate(v)
+    pub fn to_matrix(self) -> Mat4 {
+        let Quat { x, y, z, w } = self;
+        Mat4::new([
+            [1.0 - 2.0 * (y * y + z * z), 2.0 * (x * y + w * z), 2.0 * (x * z - w * y), 0.0],
+            [2.0 * (x * y - w * z), 1.0 - 2.0 * (x * x + z * z), 2.0 * (y * z + w * x), 0.0],
+            [2.0 * (x * z + w * y), 2.0 * (y * z - w * x), 1.0 - 2.0 * (x * x + y * y), 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+}
+
+impl Default for Quat {
+    fn default() -> Self {
+        Quat::IDENTITY
+    }
+}
+
+//a * b rotates by b first, then by a
+impl Mul for Quat {
+    type Output = Quat;
+    #[inline]
+    fn mul(self, b: Quat) -> Quat {
+        let a = self;
+        Quat {
+            x: a.w * b.x + a.x * b.w + a.y * b.z - a.z * b.y,
+            y: a.w * b.y - a.x * b.z + a.y * b.w + a.z * b.x,
+            z: a.w * b.z + a.x * b.y - a.y * b.x + a.z * b.w,
+            w: a.w * b.w - a.x * b.x - a.y * b.y - a.z * b.z,
+        }
+    }
+}
+
+//scale, then rotation, then translation
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Transform {
+    pub pos: Point3,
+    pub rot: Quat,
+    pub scale: Vec3,
+}
+
+impl Transform {
+    pub const IDENTITY: Transform = Transform {
+        pos: Point3::ORIGIN,
+        rot: Quat::IDENTITY,
+        scale: Vec3::new(1.0, 1.0, 1.0),
+    };
+
+    pub fn new(pos: Point3, rot: Quat, scale: Vec3) -> Self {
+        Transform { pos, rot, scale }
+    }
+    pub fn from_pos(pos: Point3) -> Self {
+        Transform { pos, ..Transform::IDENTITY }
+    }
+    #[inline]
+    pub fn apply(&self, p: Point3) -> Point3 {
+        self.pos + self.rot.rotate(p.to_vec() * self.scale)
+    }
+    //self placed inside parent, exact unless parent scales unevenly and self is rotated
+    pub fn compose(&self, parent: &Transform) -> Self {
+        Transform {
+            pos: parent.apply(self.pos),
+            rot: (parent.rot * self.rot).normalize(),
+            scale: self.scale * parent.scale,
+        }
+    }
+    //exact for an even scale, compose(inverse) gives back the identity
+    pub fn inverse(&self) -> Self {
+        let rot = self.rot.conjugate();
+        let scale = Vec3::new(1.0 / self.scale.x, 1.0 / self.scale.y, 1.0 / self.scale.z);
+        Transform {
+            pos: Point3::from_vec(rot.rotate(-self.pos.to_vec()) * scale),
+            rot,
+            scale,
+        }
+    }
+    //t.matrix().transform_point(p) == t.apply(p)
+    pub fn matrix(&self) -> Mat4 {
+        let mut m = Mat4::scaling(self.scale) * self.rot.to_matrix();
+        m.m[3] = [self.pos.x, self.pos.y, self.pos.z, 1.0];
+        m
+    }
+    //inverse transpose of the rotation and scale, for normals
+    pub fn normal_matrix(&self) -> Mat4 {
+        let s = self.scale;
+        Mat4::scaling(Vec3::new(1.0 / s.x, 1.0 / s.y, 1.0 / s.z)) * self.rot.to_matrix()
+    }
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Transform::IDENTITY
     }
 }
 
@@ -208,31 +760,115 @@ pub fn clamp<T:PartialOrd>(val: T, min_val: T, max_val: T) -> T{
         val
     }
 }
-pub fn inverse4x4(mat: [[f32; 4]; 4]) -> [[f32; 4]; 4] {
-    let mut inv = [[0.0; 4]; 4];
-    let mut det = 0.0;
-
-    for i in 0..4 {
-        for j in 0..4 {
-            let mut submat = [[0.0; 3]; 3];
-            let mut k = 0;
-            let mut l = 0;
-            for p in 0..4 {
-                for q in 0..4 {
-                    if p != i && q != j {
-                        submat[k][l] = mat[p][q];
-                        l += 1;
-                        if l == 3 {
-                            k += 1;
-                            l = 0;
-                        }
-                    }
-                }
-            }
-            let subdet = submat[0][0]*(submat[1][1]*submat[2][2]-submat[1][2]*submat[2][1]) - submat[0][1]*(submat[1][0]*submat[2][2]-submat[1][2]*submat[2][0]) + submat[0][2]*(submat[1][0]*submat[2][1]-submat[1][1]*submat[2][0]);
-            det += ((i+j) % 2 == 0) as i32 as f32 * subdet * mat[i][j];
-            inv[j][i] = ((((i+j) % 2) == 0) as i32 as f32 * subdet) / det;
-        }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::FRAC_PI_2;
+
+    fn close(a: Mat4, b: Mat4) -> bool {
+        a.m.iter().flatten().zip(b.m.iter().flatten()).all(|(x, y)| (x - y).abs() < 1e-5)
+    }
+    fn near(a: Vec3, b: Vec3) -> bool {
+        (a - b).length() < 1e-5
+    }
+    fn messy() -> Mat4 {
+        Mat4::scaling(Vec3::new(2.0, 0.5, 3.0)) * Mat4::rotation_x(0.3) * Mat4::rotation_y(-1.1) * Mat4::translation(Vec3::new(4.0, -2.0, 7.0))
+    }
+
+    #[test]
+    fn inverse_undoes() {
+        let m = messy();
+        assert!(close(m * m.inverse().unwrap(), Mat4::IDENTITY));
+        assert!(close(m.inverse().unwrap() * m, Mat4::IDENTITY));
+        let p = Mat4::perspective(90.0, 100.0, 0.1, 800.0, 600.0);
+        assert!(close(p * p.inverse().unwrap(), Mat4::IDENTITY));
+        assert!(Mat4::scaling(Vec3::new(1.0, 0.0, 1.0)).inverse().is_none());
+    }
+
+    #[test]
+    fn determinant() {
+        assert_eq!(Mat4::IDENTITY.determinant(), 1.0);
+        assert!((messy().determinant() - 3.0).abs() < 1e-4);
+        assert!((Mat4::rotation_z(0.7).determinant() - 1.0).abs() < 1e-6);
+        //swapping two rows flips the sign
+        let mut m = messy();
+        m.m.swap(0, 1);
+        assert!((m.determinant() + 3.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn inverse_rigid_matches_inverse() {
+        let m = Mat4::rotation_x(0.3) * Mat4::rotation_z(1.2) * Mat4::translation(Vec3::new(1.0, 2.0, -3.0));
+        assert!(close(m.inverse_rigid(), m.inverse().unwrap()));
+    }
+
+    #[test]
+    fn ortho_depth() {
+        let m = Mat4::ortho(2.0, 4.0, 1.0, 5.0);
+        let near = Vec4::new(2.0, 4.0, 1.0, 1.0) * m;
+        let far = Vec4::new(0.0, 0.0, 5.0, 1.0) * m;
+        assert!(near.x == -1.0 && near.y == -1.0 && near.z.abs() < 1e-6 && near.w == 1.0, "{:?}", near);
+        assert!((far.z - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn point_at_faces_target() {
+        let (pos, target) = (Point3::new(1.0, 2.0, 3.0), Point3::new(4.0, 2.0, -1.0));
+        let m = Mat4::point_at(pos, target, Vec3::Y);
+        //forward in local space lands on the target, the origin on pos
+        assert!(near(m.transform_point(Point3::new(0.0, 0.0, 5.0)).to_vec(), target.to_vec()));
+        assert!(near(m.transform_point(Point3::ORIGIN).to_vec(), pos.to_vec()));
+        assert!(near(m.transform_vector(Vec3::Y), Vec3::Y));
+        //its inverse brings the target in front of the camera
+        let view = m.inverse_rigid();
+        assert!(near(view.transform_point(target).to_vec(), Vec3::new(0.0, 0.0, 5.0)));
+    }
+
+    #[test]
+    fn quat_and_matrix_agree() {
+        let q = Quat::from_axis_angle(Vec3::new(1.0, 2.0, -0.5), 0.8);
+        let v = Vec3::new(0.3, -1.0, 2.0);
+        assert!(near(q.to_matrix().transform_vector(v), q.rotate(v)));
+        assert!(near(q.conjugate().rotate(q.rotate(v)), v));
+        //a quarter about y takes x to -z
+        let y = Quat::from_axis_angle(Vec3::Y, FRAC_PI_2);
+        assert!(near(y.rotate(Vec3::X), -Vec3::Z));
+        assert!(close(y.to_matrix(), Mat4::rotation_y(FRAC_PI_2)));
+        //products apply right to left
+        let x = Quat::from_axis_angle(Vec3::X, FRAC_PI_2);
+        assert!(near((x * y).rotate(v), x.rotate(y.rotate(v))));
+        assert!(close((x * y).to_matrix(), y.to_matrix() * x.to_matrix()));
+    }
+
+    #[test]
+    fn euler_matches_matrices() {
+        let (x, y, z) = (0.4, -1.3, 2.2);
+        let m = Mat4::rotation_z(z) * Mat4::rotation_y(y) * Mat4::rotation_x(x);
+        assert!(close(Quat::from_euler(x, y, z).to_matrix(), m));
+    }
+
+    #[test]
+    fn slerp_ends_and_middle() {
+        let (a, b) = (Quat::IDENTITY, Quat::from_axis_angle(Vec3::Z, FRAC_PI_2));
+        assert!(close(a.slerp(b, 0.0).to_matrix(), a.to_matrix()));
+        assert!(close(a.slerp(b, 1.0).to_matrix(), b.to_matrix()));
+        let half = Quat::from_axis_angle(Vec3::Z, FRAC_PI_2 * 0.5);
+        assert!(close(a.slerp(b, 0.5).to_matrix(), half.to_matrix()));
+    }
+
+    #[test]
+    fn transform_matrix_and_inverse() {
+        let t = Transform::new(Point3::new(1.0, -2.0, 3.0), Quat::from_euler(0.2, 0.5, -0.3), Vec3::new(2.0, 2.0, 2.0));
+        let p = Point3::new(0.5, 1.5, -4.0);
+        assert!(near(t.matrix().transform_point(p).to_vec(), t.apply(p).to_vec()));
+        assert!(near(t.inverse().apply(t.apply(p)).to_vec(), p.to_vec()));
+        assert!(close(t.inverse().matrix(), t.matrix().inverse().unwrap()));
+        let id = t.compose(&t.inverse());
+        assert!(near(id.apply(p).to_vec(), p.to_vec()));
+        //compose places self inside the parent
+        let child = Transform::from_pos(Point3::new(0.0, 1.0, 0.0));
+        assert!(near(child.compose(&t).apply(p).to_vec(), t.apply(child.apply(p)).to_vec()));
+        assert!(close(child.compose(&t).matrix(), child.matrix() * t.matrix()));
     }
-    inv
 }
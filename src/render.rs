@@ -0,0 +1,491 @@
+use crate::color::{Rgba, ToneMap};
+use crate::depth::{DepthBuffer, DepthMode};
+use crate::drawing::DrawTri;
+use crate::ops::{Mat4, Point3, Tri3d, Vec3, Vec4};
+use crate::world::{clip_tri, Engine};
+use crate::scene::Instance;
+use crate::material::{Cutout, Material};
+use crate::mesh::Vertex;
+use crate::simd::{self, SimdLevel};
+use crate::texture::{ColorSpace, Sampler, Texture};
+use sdl2::pixels::Color;
+use std::thread;
+
+//rows of pixels per screen tile, tiles span the full width
+pub const TILE_ROWS: usize = 16;
+
+//plain RGB24 color buffer plus the per-pixel depth and transparency state, no window needed
+//shading goes into the linear hdr buffer, color only gets the tone mapped srgb result
+pub struct FrameTarget {
+    pub width: usize,
+    pub height: usize,
+    pub color: Vec<u8>,
+    pub hdr: Vec<Rgba>,
+    pub depth: DepthBuffer,
+    //weighted sum of translucent colors (rgb) and weights (a), and the product of their (1 - alpha)
+    pub accum: Vec<Rgba>,
+    pub revealage: Vec<f32>,
+}
+
+impl FrameTarget {
+    pub fn new(width: usize, height: usize) -> Self {
+        FrameTarget {
+            width,
+            height,
+            color: vec![0; width * height * 3],
+            hdr: vec![Rgba::BLACK; width * height],
+            depth: DepthBuffer::new(width, height),
+            accum: vec![Rgba::new(0.0, 0.0, 0.0, 0.0); width * height],
+            revealage: vec![1.0; width * height],
+        }
+    }
+    pub fn clear(&mut self) {
+        self.color.fill(0);
+        self.hdr.fill(Rgba::BLACK);
+        self.depth.clear();
+        self.accum.fill(Rgba::new(0.0, 0.0, 0.0, 0.0));
+        self.revealage.fill(1.0);
+    }
+    pub fn pixel(&self, x: usize, y: usize) -> Color {
+        let i = 3 * (x + self.width * y);
+        Color::RGB(self.color[i], self.color[i + 1], self.color[i + 2])
+    }
+    //"tiles" are full width bands of `rows` rows rather than 2d squares, so each borrows one
+    //contiguous part of every buffer, top to bottom, none when the target or `rows` is empty
+    pub fn tiles(&mut self, rows: usize, sampler: Sampler, simd: SimdLevel) -> Vec<Tile<'_>> {
+        let w = self.width;
+        if w == 0 || rows == 0 {
+            return Vec::new();
+        }
+        let depth_mode = self.depth.mode;
+        self.color
+            .chunks_mut(3 * w * rows)
+            .zip(self.hdr.chunks_mut(w * rows))
+            .zip(self.depth.data.chunks_mut(w * rows))
+            .zip(self.accum.chunks_mut(w * rows))
+            .zip(self.revealage.chunks_mut(w * rows))
+            .enumerate()
+            .map(|(i, ((((color, hdr), depth), accum), revealage))| Tile {
+                y0: i * rows,
+                width: w,
+                height: depth.len() / w,
+                color,
+                hdr,
+                depth,
+                depth_mode,
+                sampler,
+                simd,
+                accum,
+                revealage,
+            })
+            .collect()
+    }
+}
+
+//part of a FrameTarget starting at row y0, buffers are indexed relative to that row
+pub struct Tile<'a> {
+    pub y0: usize,
+    pub width: usize,
+    pub height: usize,
+    pub color: &'a mut [u8],
+    pub hdr: &'a mut [Rgba],
+    pub depth: &'a mut [f32],
+    pub depth_mode: DepthMode,
+    pub sampler: Sampler,
+    pub simd: SimdLevel,
+    pub accum: &'a mut [Rgba],
+    pub revealage: &'a mut [f32],
+}
+
+impl Tile<'_> {
+    //blends the accumulated translucent layers over the opaque color, then tone maps and encodes to srgb
+    pub fn resolve(&mut self, tone_map: ToneMap, exposure: f32) {
+        for (i, &r) in self.revealage.iter().enumerate() {
+            let mut px = self.hdr[i];
+            if r < 1.0 {
+                let acc = self.accum[i];
+                let avg = acc / acc.a.max(1e-5);
+                px = avg * (1.0 - r) + px * r;
+                self.hdr[i] = px;
+            }
+            let out = px.map(|v| tone_map.apply(v * exposure)).to_srgb();
+            self.color[3 * i] = out.r;
+            self.color[3 * i + 1] = out.g;
+            self.color[3 * i + 2] = out.b;
+        }
+    }
+}
+
+//a clipped triangle in screen space, with its view space twin for lighting and its texture
+pub struct ScreenTri<'a> {
+    pub tri: Tri3d,
+    pub info: Tri3d,
+    pub tex: &'a Texture,
+    //None for meshes built without materials, shading then falls back to the vertex colors and the tri's rfl
+    pub material: Option<&'a Material>,
+    //tangent space normals, from the material's map_Bump
+    pub normal_map: Option<&'a Texture>,
+    pub depth_write: bool,
+}
+
+pub struct Renderer {
+    pub world_up: Vec3,
+    pub threads: usize,
+    pub tone_map: ToneMap,
+    //linear scale applied before tone mapping
+    pub exposure: f32,
+    pub sampler: Sampler,
+    //vertex transforms and span interpolation, detect() picks the widest the cpu has
+    pub simd: SimdLevel,
+}
+
+impl Renderer {
+    pub fn new(world_up: Vec3) -> Self {
+        Renderer {
+            world_up,
+            threads: thread::available_parallelism().map(|n| n.get()).unwrap_or(1),
+            tone_map: ToneMap::Aces,
+            exposure: 1.0,
+            sampler: Sampler::default(),
+            simd: SimdLevel::detect(),
+        }
+    }
+
+    //shadow maps first, then clip, project and rasterize every mesh into the target
+    pub fn render(&self, engine: &mut Engine, target: &mut FrameTarget) -> Result<(), String> {
+        self.load_textures(engine)?;
+        engine.scene.update();
+        let worlds = self.world_vertices(engine);
+        self.shadow_pass(engine, &worlds, target.width, target.height);
+        target.depth.set_range(engine.camera.clip_distance, engine.camera.render_distance);
+        target.clear();
+
+        let engine: &Engine = engine;
+        let mut tris = Vec::new();
+        for (inst, world) in engine.instances().into_iter().zip(&worlds) {
+            self.project_mesh(engine, inst, world, target.width, target.height, &mut tris);
+        }
+        self.rasterize(engine, &tris, target);
+        Ok(())
+    }
+
+    //bins triangles into tiles and shades the tiles in parallel
+    //tiles go to threads round-robin, so every thread gets bands from all over the screen
+    //opaque triangles come first in every bin so translucent ones are tested against finished depth
+    pub fn rasterize(&self, engine: &Engine, tris: &[ScreenTri], target: &mut FrameTarget) {
+        let tiles = target.tiles(TILE_ROWS, self.sampler, self.simd);
+        if tiles.is_empty() {
+            return;
+        }
+        let mut bins: Vec<Vec<usize>> = vec![Vec::new(); tiles.len()];
+        let opaque = tris.iter().enumerate().filter(|(_, st)| st.info.trs <= 0.0);
+        let translucent = tris.iter().enumerate().filter(|(_, st)| st.info.trs > 0.0);
+        for (i, st) in opaque.chain(translucent) {
+            let ps = st.tri.ps;
+            let lo = ps[0].y.min(ps[1].y).min(ps[2].y).floor() as i32;
+            let hi = ps[0].y.max(ps[1].y).max(ps[2].y).floor() as i32;
+            //same rows the rasterizer walks
+            let first = (lo + 1).max(0) as usize / TILE_ROWS;
+            let last = (hi.max(0) as usize / TILE_ROWS).min(bins.len() - 1);
+            if hi > lo {
+                for bin in bins.iter_mut().take(last + 1).skip(first) {
+                    bin.push(i);
+                }
+            }
+        }
+
+        let threads = self.threads.max(1);
+        let (tone_map, exposure) = (self.tone_map, self.exposure);
+        let mut jobs: Vec<Vec<(Tile, &Vec<usize>)>> = (0..threads).map(|_| Vec::new()).collect();
+        for (i, job) in tiles.into_iter().zip(&bins).enumerate() {
+            jobs[i % threads].push(job);
+        }
+        thread::scope(|s| {
+            for job in jobs {
+                s.spawn(move || {
+                    for (mut tile, bin) in job {
+                        for &i in bin {
+                            tile.textured_triangle(&tris[i], engine);
+                        }
+                        tile.resolve(tone_map, exposure);
+                    }
+                });
+            }
+        });
+    }
+
+    //only decodes what is not in the cache yet
+    pub fn load_textures(&self, engine: &mut Engine) -> Result<(), String> {
+        for mesh in &engine.objects {
+            if !mesh.tex.is_empty() {
+                engine.textures.load(&mesh.tex, ColorSpace::Srgb)?;
+            }
+            for m in &mesh.materials {
+                for (p, space) in m.maps() {
+                    engine.textures.load(p, space)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    //the vertices of every mesh in engine.instances() moved to world space, for both passes
+    //expects the scene to be updated
+    pub fn world_vertices(&self, engine: &Engine) -> Vec<Vec<Vertex>> {
+        engine.instances().iter()
+            .map(|inst| simd::transform_vertices(self.simd, &inst.mesh.geometry.vertices, inst.world, inst.normal))
+            .collect()
+    }
+
+    //`worlds` as returned by world_vertices, width and height are the target's, sun cascades fit its view
+    pub fn shadow_pass(&self, engine: &mut Engine, worlds: &[Vec<Vertex>], width: usize, height: usize) {
+        //taken out so the maps can be written while meshes and textures are read
+        let mut lights = std::mem::take(&mut engine.lights);
+        let textures = &engine.textures;
+        let mut casters: Vec<(Tri3d, Option<Cutout>)> = Vec::new();
+        for (Instance { mesh: obj, .. }, verts) in engine.instances().into_iter().zip(worlds) {
+            //same texture choice as project_mesh, only needed for cutout materials
+            let otex = textures.get(&obj.tex).unwrap_or_else(|| textures.white());
+            let cutouts: Vec<Option<Cutout>> = obj.materials.iter()
+                .map(|m| {
+                    let tex = m.map_kd.as_ref().and_then(|p| textures.get(p)).unwrap_or(otex);
+                    m.cutout(tex, textures, self.sampler)
+                })
+                .collect();
+            for tri in obj.geometry.assemble(verts) {
+                let cutout = cutouts.get(tri.mat).copied().flatten();
+                casters.push((tri, cutout));
+            }
+        }
+        for light in &mut lights {
+            light.update_views(&engine.camera, width as f32 / height as f32);
+            light.clear_shadows();
+            for (tri, cutout) in &casters {
+                light.edit_shadow_buffer(*tri, *cutout);
+            }
+        }
+        engine.lights = lights;
+    }
+
+    //`world` is inst's vertices in world space, culling and clipping happen there too
+    //so the clipped triangles keep world positions and directions for lighting
+    fn project_mesh<'a>(&self, engine: &'a Engine, inst: Instance<'a>, world: &[Vertex], width: usize, height: usize, out: &mut Vec<ScreenTri<'a>>) {
+        let mesh = inst.mesh;
+        let cam = &engine.camera;
+        let width = width as f32;
+        let height = height as f32;
+        let mat3d = Mat4::perspective(cam.fov, cam.render_distance, cam.clip_distance, width, height);
+
+        let t = (cam.fov.to_radians()*0.5).tan();
+        let aspect = width/height;
+        //in view space, moved to world space below
+        let w_clip = [
+            (Point3::new(0.0, 0.0, cam.render_distance), Vec3::new(0.0, 0.0, -1.0)),
+            (Point3::new(0.0, 0.0, cam.clip_distance), Vec3::new(0.0, 0.0, 1.0)),
+
+            (Point3::new(aspect*t, 0.0, cam.clip_distance), Vec3::new(-t, 0.0, aspect)),
+            (Point3::new(-aspect*t, 0.0, cam.clip_distance), Vec3::new(t, 0.0, aspect)),
+
+            (Point3::new(0.0, -t, cam.clip_distance), Vec3::new(0.0, t, 1.0)),
+            (Point3::new(0.0, t, cam.clip_distance), Vec3::new(0.0, -t, 1.0)),
+        ];
+
+        let ew = width*0.5; let eh = height*0.5;
+        let cam_pmat = Mat4::point_at(cam.pos, cam.pos + cam.dir, self.world_up);
+        let w_clip = w_clip.map(|(p, n)| (cam_pmat.transform_point(p), cam_pmat.transform_vector(n)));
+        let view_proj = cam_pmat.inverse_rigid() * mat3d;
+
+        let otex = engine.textures.get(&mesh.tex).unwrap_or_else(|| engine.textures.white());
+        //materials without a map_Kd use the mesh texture
+        let mtex : Vec<Option<&Texture>> = mesh.materials.iter()
+            .map(|m| m.map_kd.as_ref().and_then(|p| engine.textures.get(p)))
+            .collect();
+        let nmaps : Vec<Option<&Texture>> = mesh.materials.iter()
+            .map(|m| m.map_bump.as_ref().and_then(|p| engine.textures.get(p)))
+            .collect();
+        //every vertex is projected once, only corners made by clipping are projected on their own
+        let projected: Vec<Vec4> = world.iter().map(|v| Vec4::from(v.pos) * view_proj).collect();
+        //triangles are only put together for culling and clipping, the vertices are shared
+        for (ids, vt) in mesh.geometry.assemble_indexed(world) {
+            //front faces, whose winding normal points back at the camera
+            if vt.normal().dot(vt.center() - cam.pos) <= 0.0{
+                let mut clipped = vec![vt];
+                let trs = &mut [Tri3d::empty(), Tri3d::empty()];
+                for plane in &w_clip{
+                    for _n in 0..clipped.len(){
+                        let t_clipped = clip_tri(plane.0, plane.1, clipped[0], trs);
+                        clipped.remove(0);
+                        for b in trs.iter().take(t_clipped){
+                            clipped.push(*b);
+                        }
+                    }
+                }
+                for tri in clipped{
+                    let clip = tri.ps.map(|p| match (0..3).find(|&j| vt.ps[j] == p) {
+                        Some(j) => projected[ids[j]],
+                        None => Vec4::from(p) * view_proj,
+                    });
+                    //w is the view depth
+                    if (tri.trs-1.0).abs() > f32::EPSILON && !(clip[0].w <= 0.0 || clip[1].w <= 0.0 || clip[2].w <= 0.0){
+
+                        let mut t = tri;
+                        let t03 = 1.0/clip[0].w; let t13 = 1.0/clip[1].w; let t23 = 1.0/clip[2].w;
+
+                        t.uvs[0][1] *= t03;
+                        t.uvs[1][1] *= t13;
+                        t.uvs[2][1] *= t23;
+
+                        t.uvs[0][0] *= t03;
+                        t.uvs[1][0] *= t13;
+                        t.uvs[2][0] *= t23;
+
+                        t.uvs[0][2] = t03;
+                        t.uvs[1][2] = t13;
+                        t.uvs[2][2] = t23;
+
+                        //pixels across, pixels down and z/w
+                        for (p, c) in t.ps.iter_mut().zip(&clip) {
+                            let iw = 1.0/c.w;
+                            *p = Point3::new((c.x*iw + 1.0)*ew, (c.y*iw + 1.0)*eh, c.z*iw);
+                        }
+
+                        let tex = mtex.get(tri.mat).copied().flatten().unwrap_or(otex);
+                        out.push(ScreenTri {
+                            tri: t,
+                            info: tri,
+                            tex,
+                            material: mesh.materials.get(tri.mat),
+                            normal_map: nmaps.get(tri.mat).copied().flatten(),
+                            depth_write: mesh.depth_write,
+                        });
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::{Camera, Mesh};
+
+    //32x32 looking down +z, with a white ambient as the only light
+    fn engine() -> Engine {
+        let camera = Camera {
+            fov: 90.0,
+            pos: Point3::new(0.0, 0.0, 0.0),
+            dir: Vec3::new(0.0, 0.0, 1.0),
+            vel: Vec3::ZERO,
+            rot_vel: Vec3::ZERO,
+            clip_distance: 0.5,
+            render_distance: 100.0,
+            window_height: 32.0,
+            window_width: 32.0,
+        };
+        let mut engine = Engine::new(camera);
+        engine.ambient = Rgba::WHITE;
+        engine
+    }
+
+    //one red, untextured triangle at z 4 facing the camera
+    fn render_red(ps: [Point3; 3]) -> FrameTarget {
+        let mut engine = engine();
+        let tri = Tri3d::new(ps, [[0.0, 0.0, 1.0]; 3], [Vec3::new(0.0, 0.0, -1.0); 3], Rgba::rgb(1.0, 0.0, 0.0), 0.0, 0.0);
+        engine.objects.push(Mesh::new(vec![tri], String::new()));
+        let mut target = FrameTarget::new(32, 32);
+        Renderer::new(Vec3::Y).render(&mut engine, &mut target).unwrap();
+        target
+    }
+
+    fn covered(target: &FrameTarget) -> Vec<(usize, usize)> {
+        (0..target.height)
+            .flat_map(|y| (0..target.width).map(move |x| (x, y)))
+            .filter(|&(x, y)| target.pixel(x, y) != Color::RGB(0, 0, 0))
+            .collect()
+    }
+
+    #[test]
+    fn untextured_triangle() {
+        let target = render_red([Point3::new(-1.0, -1.0, 4.0), Point3::new(-1.0, 1.0, 4.0), Point3::new(1.0, 1.0, 4.0)]);
+        let covered = covered(&target);
+        //half of the 8x8 pixel square the triangle's bounds project to
+        assert!(covered.len() > 20 && covered.len() < 44, "{} pixels", covered.len());
+        for &(x, y) in &covered {
+            assert!((12..20).contains(&x) && (12..20).contains(&y), "pixel at {} {}", x, y);
+            let c = target.pixel(x, y);
+            assert!(c.r > 200 && c.g == 0 && c.b == 0, "{:?}", c);
+        }
+    }
+
+    #[test]
+    fn vertex_colors() {
+        let mut engine = engine();
+        let mut tri = Tri3d::new(
+            [Point3::new(-3.0, -3.0, 4.0), Point3::new(-3.0, 3.0, 4.0), Point3::new(3.0, 3.0, 4.0)],
+            [[0.0, 0.0, 1.0]; 3],
+            [Vec3::new(0.0, 0.0, -1.0); 3],
+            Rgba::WHITE,
+            0.0,
+            0.0,
+        );
+        tri.cols = [Rgba::RED, Rgba::GREEN, Rgba::BLUE];
+        engine.objects.push(Mesh::new(vec![tri], String::new()));
+        let mut target = FrameTarget::new(32, 32);
+        Renderer::new(Vec3::Y).render(&mut engine, &mut target).unwrap();
+        //blended across the triangle, each corner keeps to its own color
+        let px: Vec<Color> = covered(&target).iter().map(|&(x, y)| target.pixel(x, y)).collect();
+        assert!(px.iter().any(|c| c.r > 200 && c.g < 100 && c.b < 100));
+        assert!(px.iter().any(|c| c.g > 200 && c.r < 100 && c.b < 100));
+        assert!(px.iter().any(|c| c.b > 200 && c.r < 100 && c.g < 100));
+        assert!(px.iter().any(|c| c.r > 100 && c.g > 100));
+    }
+
+    #[test]
+    fn empty_target() {
+        for (w, h) in [(0, 8), (8, 0), (0, 0)] {
+            let mut target = FrameTarget::new(w, h);
+            assert!(target.tiles(TILE_ROWS, Sampler::default(), SimdLevel::Scalar).is_empty());
+        }
+        assert!(FrameTarget::new(8, 8).tiles(0, Sampler::default(), SimdLevel::Scalar).is_empty());
+    }
+
+    #[test]
+    fn full_screen_triangle() {
+        //reaches well past every edge of the view, so row 0 and column 0 are covered too
+        let target = render_red([Point3::new(-20.0, -20.0, 4.0), Point3::new(-20.0, 60.0, 4.0), Point3::new(60.0, -20.0, 4.0)]);
+        assert_eq!(covered(&target).len(), 32 * 32);
+    }
+
+    #[test]
+    fn mtl_map_d_cuts_out() {
+        //a .mtl with a map_d and nothing else about alpha still cuts out, the obj covers the view at z 4
+        let dir = std::env::temp_dir().join(format!("rust3denginetest_cutout_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("quad.mtl"), "newmtl cut\nKd 1 0 0\nmap_d alpha.png\n").unwrap();
+        let obj = "mtllib quad.mtl\nv -5 -5 4\nv -5 5 4\nv 5 5 4\nv 5 -5 4\n\
+                   vt 0 0\nvt 0 1\nvt 1 1\nvt 1 0\nusemtl cut\nf 1/1 2/2 3/3 4/4\n";
+        std::fs::write(dir.join("quad.obj"), obj).unwrap();
+        let path = dir.join("quad.obj").to_string_lossy().into_owned();
+        let mesh = Mesh::load_obj_file(path, String::new(), Rgba::WHITE, 0.0, 0.0).unwrap_or_else(|e| panic!("{}", e));
+        std::fs::remove_dir_all(&dir).ok();
+
+        let mut engine = engine();
+        //transparent on one half, opaque on the other
+        let mut alpha = Texture::new(2, 1, 2, 1, vec![0, 255], ColorSpace::Linear);
+        alpha.wrap = [crate::texture::Wrap::Clamp; 2];
+        let key = mesh.materials[1].map_d.clone().unwrap();
+        engine.textures.insert(&key, alpha);
+        engine.objects.push(mesh);
+        let mut target = FrameTarget::new(32, 32);
+        Renderer::new(Vec3::Y).render(&mut engine, &mut target).unwrap();
+
+        let covered = covered(&target);
+        assert!(covered.len() > 400 && covered.len() < 624, "{} pixels", covered.len());
+        //the cut runs down the view, every row keeps the same side of it
+        let rows: Vec<usize> = (0..32).map(|y| covered.iter().filter(|&&(_, py)| py == y).count()).collect();
+        assert!(rows.iter().all(|&n| n.abs_diff(rows[0]) <= 1), "{:?}", rows);
+        let side = covered[0].0 == 0;
+        assert!(covered.iter().all(|&(x, _)| (x < 16) == side), "{:?}", covered);
+    }
+}
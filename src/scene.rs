@@ -0,0 +1,186 @@
+use crate::ops::{Mat4, Point3, Transform};
+use crate::world::Mesh;
+
+pub type NodeId = usize;
+
+//one mesh to draw and where, see Engine::instances
+#[derive(Copy, Clone)]
+pub struct Instance<'a> {
+    pub mesh: &'a Mesh,
+    pub world: Mat4,
+    pub normal: Mat4,
+}
+
+//a local transform relative to the parent, and optionally a mesh drawn with it
+//meshes keep the vertices they were loaded with, the node's world matrix is applied when drawing
+pub struct Node {
+    pub name: String,
+    pub transform: Transform,
+    //index into Engine::objects, stays valid as long as meshes are only pushed there
+    pub mesh: Option<usize>,
+}
+
+impl Node {
+    pub fn new(name: &str) -> Self {
+        Node {
+            name: name.to_string(),
+            transform: Transform::IDENTITY,
+            mesh: None,
+        }
+    }
+    pub fn with_mesh(name: &str, mesh: usize) -> Self {
+        Node {
+            mesh: Some(mesh),
+            ..Node::new(name)
+        }
+    }
+}
+
+//where a node hangs and where that puts it, kept apart so nodes stay plain values
+struct Link {
+    parent: Option<NodeId>,
+    children: Vec<NodeId>,
+    world: Mat4,
+    //inverse transpose of the world rotation and scale, for normals
+    world_normal: Mat4,
+}
+
+//nodes live in one list and refer to each other by index, ids stay valid as nodes are added
+#[derive(Default)]
+pub struct Scene {
+    nodes: Vec<Node>,
+    links: Vec<Link>,
+}
+
+impl Scene {
+    pub fn new() -> Self {
+        Scene { nodes: Vec::new(), links: Vec::new() }
+    }
+    pub fn add(&mut self, node: Node, parent: Option<NodeId>) -> NodeId {
+        let id = self.nodes.len();
+        self.nodes.push(node);
+        self.links.push(Link {
+            parent: None,
+            children: Vec::new(),
+            world: Mat4::IDENTITY,
+            world_normal: Mat4::IDENTITY,
+        });
+        self.set_parent(id, parent);
+        id
+    }
+    //moves the node with all of its children, a parent inside its own subtree is refused
+    pub fn set_parent(&mut self, id: NodeId, parent: Option<NodeId>) -> bool {
+        let mut p = parent;
+        while let Some(i) = p {
+            if i == id {
+                return false;
+            }
+            p = self.links[i].parent;
+        }
+        if let Some(old) = self.links[id].parent.take() {
+            self.links[old].children.retain(|&c| c != id);
+        }
+        if let Some(p) = parent {
+            self.links[p].children.push(id);
+        }
+        self.links[id].parent = parent;
+        true
+    }
+    pub fn parent(&self, id: NodeId) -> Option<NodeId> {
+        self.links[id].parent
+    }
+    pub fn children(&self, id: NodeId) -> &[NodeId] {
+        &self.links[id].children
+    }
+    pub fn node(&self, id: NodeId) -> &Node {
+        &self.nodes[id]
+    }
+    pub fn node_mut(&mut self, id: NodeId) -> &mut Node {
+        &mut self.nodes[id]
+    }
+    pub fn nodes(&self) -> &[Node] {
+        &self.nodes
+    }
+    pub fn nodes_mut(&mut self) -> &mut [Node] {
+        &mut self.nodes
+    }
+    pub fn find(&self, name: &str) -> Option<NodeId> {
+        self.nodes.iter().position(|n| n.name == name)
+    }
+    //world matrices from the roots down, a child is local * parent's world
+    pub fn update(&mut self) {
+        let mut stack: Vec<NodeId> = (0..self.nodes.len()).filter(|&i| self.links[i].parent.is_none()).collect();
+        while let Some(i) = stack.pop() {
+            let (world, normal) = match self.links[i].parent {
+                Some(p) => (self.links[p].world, self.links[p].world_normal),
+                None => (Mat4::IDENTITY, Mat4::IDENTITY),
+            };
+            let t = &self.nodes[i].transform;
+            let l = &mut self.links[i];
+            l.world = t.matrix() * world;
+            l.world_normal = t.normal_matrix() * normal;
+            stack.extend_from_slice(&l.children);
+        }
+    }
+    //as of the last update
+    pub fn world(&self, id: NodeId) -> Mat4 {
+        self.links[id].world
+    }
+    pub fn world_normal(&self, id: NodeId) -> Mat4 {
+        self.links[id].world_normal
+    }
+    //world position of the node's origin
+    pub fn world_pos(&self, id: NodeId) -> Point3 {
+        self.links[id].world.transform_point(Point3::ORIGIN)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ops::{Quat, Vec3};
+    use std::f32::consts::FRAC_PI_2;
+
+    fn close(a: Vec3, b: Vec3) -> bool {
+        (a - b).length() < 1e-5
+    }
+
+    #[test]
+    fn two_levels() {
+        let mut scene = Scene::new();
+        //parent turned a quarter about y and scaled 2x along x, child 1 along its parent's x
+        let turn = Quat::from_axis_angle(Vec3::Y, FRAC_PI_2);
+        let parent = scene.add(Node { transform: Transform::new(Point3::new(0.0, 0.0, 5.0), turn, Vec3::new(2.0, 1.0, 1.0)), ..Node::new("parent") }, None);
+        let child = scene.add(Node { transform: Transform::from_pos(Point3::new(1.0, 0.0, 0.0)), ..Node::new("child") }, Some(parent));
+        assert_eq!(scene.children(parent), &[child][..]);
+        scene.update();
+
+        let p = scene.node(parent).transform;
+        let want = Transform::from_pos(Point3::new(1.0, 0.0, 0.0)).matrix() * p.matrix();
+        assert_eq!(scene.world(child), want);
+        //a point on the child goes through the child's transform, then the parent's
+        let on_child = scene.world(child).transform_point(Point3::new(0.0, 1.0, 0.0));
+        assert!(close(on_child.to_vec(), p.apply(Point3::new(1.0, 1.0, 0.0)).to_vec()), "{:?}", on_child);
+        assert!(close(scene.world_pos(child).to_vec(), p.apply(Point3::new(1.0, 0.0, 0.0)).to_vec()));
+
+        //normals stay perpendicular to surfaces through the uneven scale
+        let (t, n) = (Vec3::new(1.0, 1.0, 0.0), Vec3::new(1.0, -1.0, 0.0));
+        let wt = scene.world(child).transform_vector(t);
+        let wn = scene.world_normal(child).transform_vector(n);
+        assert!(wt.dot(wn).abs() < 1e-5, "{:?} {:?}", wt, wn);
+
+        //moving the parent moves the child on the next update, not before
+        let before = scene.world_pos(child);
+        scene.node_mut(parent).transform.pos = Point3::new(0.0, 3.0, 5.0);
+        assert_eq!(scene.world_pos(child), before);
+        scene.update();
+        assert!(close(scene.world_pos(child) - before, Vec3::new(0.0, 3.0, 0.0)));
+
+        //and unparenting leaves it where its own transform puts it
+        assert!(!scene.set_parent(parent, Some(child)));
+        assert!(scene.set_parent(child, None));
+        scene.update();
+        assert_eq!(scene.world_pos(child), Point3::new(1.0, 0.0, 0.0));
+        assert!(scene.children(parent).is_empty());
+    }
+}
@@ -0,0 +1,518 @@
+//batched vertex transforms and span interpolation, several values per instruction where the cpu allows
+//every level does the same float operations in the same order, so they agree to the bit with Scalar
+use crate::depth::{DepthFunc, DepthMode};
+use crate::mesh::Vertex;
+use crate::color::Rgba;
+use crate::ops::{Mat4, Point3, Vec3};
+#[cfg(target_arch = "x86_64")]
+use std::arch::x86_64::*;
+
+//pixels per Span::interpolate call
+pub const LANES: usize = 8;
+
+//instruction set the batched functions use, ordered from narrowest to widest
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SimdLevel {
+    Scalar,
+    //4 lanes
+    Sse2,
+    //8 lanes
+    Avx,
+}
+
+impl SimdLevel {
+    //widest level the running cpu has
+    pub fn detect() -> Self {
+        #[cfg(target_arch = "x86_64")]
+        {
+            if is_x86_feature_detected!("avx") {
+                return SimdLevel::Avx;
+            }
+            if is_x86_feature_detected!("sse2") {
+                return SimdLevel::Sse2;
+            }
+        }
+        SimdLevel::Scalar
+    }
+    //a level set by hand that the cpu lacks falls back instead of faulting
+    #[inline]
+    fn usable(self) -> Self {
+        self.min(SimdLevel::detect())
+    }
+}
+
+impl Default for SimdLevel {
+    fn default() -> Self {
+        SimdLevel::detect()
+    }
+}
+
+//rows of m with the last one scaled by w, 1 for points and 0 for directions
+fn rows(m: &Mat4, w: f32) -> [[f32; 4]; 4] {
+    let m = &m.m;
+    [m[0], m[1], m[2], m[3].map(|v| w * v)]
+}
+
+//v.transform(m, n) on every vertex
+pub fn transform_vertices(level: SimdLevel, vs: &[Vertex], m: Mat4, n: Mat4) -> Vec<Vertex> {
+    let mut out = Vec::with_capacity(vs.len());
+    match level.usable() {
+        #[cfg(target_arch = "x86_64")]
+        SimdLevel::Avx => unsafe { vertices_avx(&m, &n, vs, &mut out) },
+        #[cfg(target_arch = "x86_64")]
+        SimdLevel::Sse2 => unsafe { vertices_sse2(&m, &n, vs, &mut out) },
+        _ => out.extend(vs.iter().map(|v| v.transform(m, n))),
+    }
+    out
+}
+
+//one attribute of up to N vertices as a column per component, the last vertex fills any spare lanes
+#[inline]
+fn columns<const N: usize>(vs: &[Vertex], f: impl Fn(&Vertex) -> [f32; 3]) -> [[f32; N]; 3] {
+    let mut c = [[0.0; N]; 3];
+    for i in 0..N {
+        let a = f(&vs[i.min(vs.len() - 1)]);
+        c[0][i] = a[0];
+        c[1][i] = a[1];
+        c[2][i] = a[2];
+    }
+    c
+}
+
+//every entry of the rows in every lane
+#[cfg(target_arch = "x86_64")]
+#[inline]
+#[target_feature(enable = "avx")]
+unsafe fn splat_avx(r: &[[f32; 4]; 4]) -> [[__m256; 4]; 4] {
+    r.map(|r| r.map(|v| _mm256_set1_ps(v)))
+}
+
+//(x, y, z, 1 or 0) * m for 8 vertices at once, in the order Vec4 * Mat4 adds them up
+#[cfg(target_arch = "x86_64")]
+#[inline]
+#[target_feature(enable = "avx")]
+unsafe fn mul_avx(m: &[[__m256; 4]; 4], c: &[[f32; 8]; 3]) -> [__m256; 3] {
+    let (x, y, z) = (_mm256_loadu_ps(c[0].as_ptr()), _mm256_loadu_ps(c[1].as_ptr()), _mm256_loadu_ps(c[2].as_ptr()));
+    let mut out = [_mm256_setzero_ps(); 3];
+    for (j, o) in out.iter_mut().enumerate() {
+        let s = _mm256_add_ps(_mm256_mul_ps(x, m[0][j]), _mm256_mul_ps(y, m[1][j]));
+        *o = _mm256_add_ps(_mm256_add_ps(s, _mm256_mul_ps(z, m[2][j])), m[3][j]);
+    }
+    out
+}
+
+#[cfg(target_arch = "x86_64")]
+#[inline]
+#[target_feature(enable = "avx")]
+unsafe fn store_avx(v: [__m256; 3]) -> [[f32; 8]; 3] {
+    let mut out = [[0.0; 8]; 3];
+    for (o, v) in out.iter_mut().zip(v.iter()) {
+        _mm256_storeu_ps(o.as_mut_ptr(), *v);
+    }
+    out
+}
+
+#[cfg(target_arch = "x86_64")]
+#[inline]
+#[target_feature(enable = "avx")]
+unsafe fn dir_avx(m: &[[__m256; 4]; 4], c: &[[f32; 8]; 3]) -> [[f32; 8]; 3] {
+    let [x, y, z] = mul_avx(m, c);
+    let (x, y, z) = unit_avx(x, y, z);
+    store_avx([x, y, z])
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx")]
+unsafe fn vertices_avx(m: &Mat4, n: &Mat4, vs: &[Vertex], out: &mut Vec<Vertex>) {
+    let (pm, dm, dn) = (splat_avx(&rows(m, 1.0)), splat_avx(&rows(m, 0.0)), splat_avx(&rows(n, 0.0)));
+    for chunk in vs.chunks(8) {
+        let p = store_avx(mul_avx(&pm, &columns(chunk, |v| v.pos.to_array())));
+        let nr = dir_avx(&dn, &columns(chunk, |v| v.normal.to_array()));
+        let t = dir_avx(&dm, &columns(chunk, |v| v.tangent.to_array()));
+        let b = dir_avx(&dm, &columns(chunk, |v| v.bitangent.to_array()));
+        out.extend(chunk.iter().enumerate().map(|(i, v)| Vertex {
+            pos: Point3::new(p[0][i], p[1][i], p[2][i]),
+            uv: v.uv,
+            normal: Vec3::new(nr[0][i], nr[1][i], nr[2][i]),
+            tangent: Vec3::new(t[0][i], t[1][i], t[2][i]),
+            bitangent: Vec3::new(b[0][i], b[1][i], b[2][i]),
+            col: v.col,
+        }));
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+#[inline]
+#[target_feature(enable = "sse2")]
+unsafe fn splat_sse2(r: &[[f32; 4]; 4]) -> [[__m128; 4]; 4] {
+    r.map(|r| r.map(|v| _mm_set1_ps(v)))
+}
+
+//4 vertices at once
+#[cfg(target_arch = "x86_64")]
+#[inline]
+#[target_feature(enable = "sse2")]
+unsafe fn mul_sse2(m: &[[__m128; 4]; 4], c: &[[f32; 4]; 3]) -> [__m128; 3] {
+    let (x, y, z) = (_mm_loadu_ps(c[0].as_ptr()), _mm_loadu_ps(c[1].as_ptr()), _mm_loadu_ps(c[2].as_ptr()));
+    let mut out = [_mm_setzero_ps(); 3];
+    for (j, o) in out.iter_mut().enumerate() {
+        let s = _mm_add_ps(_mm_mul_ps(x, m[0][j]), _mm_mul_ps(y, m[1][j]));
+        *o = _mm_add_ps(_mm_add_ps(s, _mm_mul_ps(z, m[2][j])), m[3][j]);
+    }
+    out
+}
+
+#[cfg(target_arch = "x86_64")]
+#[inline]
+#[target_feature(enable = "sse2")]
+unsafe fn store_sse2(v: [__m128; 3]) -> [[f32; 4]; 3] {
+    let mut out = [[0.0; 4]; 3];
+    for (o, v) in out.iter_mut().zip(v.iter()) {
+        _mm_storeu_ps(o.as_mut_ptr(), *v);
+    }
+    out
+}
+
+#[cfg(target_arch = "x86_64")]
+#[inline]
+#[target_feature(enable = "sse2")]
+unsafe fn dir_sse2(m: &[[__m128; 4]; 4], c: &[[f32; 4]; 3]) -> [[f32; 4]; 3] {
+    let [x, y, z] = mul_sse2(m, c);
+    let (x, y, z) = unit_sse2(x, y, z);
+    store_sse2([x, y, z])
+}
+
+//zero length stays as it is, like Vec3::normalize
+#[cfg(target_arch = "x86_64")]
+#[inline]
+#[target_feature(enable = "avx")]
+unsafe fn unit_avx(x: __m256, y: __m256, z: __m256) -> (__m256, __m256, __m256) {
+    let l = _mm256_add_ps(_mm256_add_ps(_mm256_mul_ps(x, x), _mm256_mul_ps(y, y)), _mm256_mul_ps(z, z));
+    let l = _mm256_sqrt_ps(l);
+    let ok = _mm256_cmp_ps(l, _mm256_setzero_ps(), _CMP_GT_OQ);
+    let r = _mm256_div_ps(_mm256_set1_ps(1.0), l);
+    (
+        _mm256_blendv_ps(x, _mm256_mul_ps(x, r), ok),
+        _mm256_blendv_ps(y, _mm256_mul_ps(y, r), ok),
+        _mm256_blendv_ps(z, _mm256_mul_ps(z, r), ok),
+    )
+}
+
+#[cfg(target_arch = "x86_64")]
+#[inline]
+#[target_feature(enable = "sse2")]
+unsafe fn unit_sse2(x: __m128, y: __m128, z: __m128) -> (__m128, __m128, __m128) {
+    let l = _mm_add_ps(_mm_add_ps(_mm_mul_ps(x, x), _mm_mul_ps(y, y)), _mm_mul_ps(z, z));
+    let l = _mm_sqrt_ps(l);
+    let ok = _mm_cmpgt_ps(l, _mm_setzero_ps());
+    let r = _mm_div_ps(_mm_set1_ps(1.0), l);
+    //no blendv before sse4.1
+    let pick = |a: __m128, b: __m128| _mm_or_ps(_mm_and_ps(ok, b), _mm_andnot_ps(ok, a));
+    (pick(x, _mm_mul_ps(x, r)), pick(y, _mm_mul_ps(y, r)), pick(z, _mm_mul_ps(z, r)))
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "sse2")]
+unsafe fn vertices_sse2(m: &Mat4, n: &Mat4, vs: &[Vertex], out: &mut Vec<Vertex>) {
+    let (pm, dm, dn) = (splat_sse2(&rows(m, 1.0)), splat_sse2(&rows(m, 0.0)), splat_sse2(&rows(n, 0.0)));
+    for chunk in vs.chunks(4) {
+        let p = store_sse2(mul_sse2(&pm, &columns(chunk, |v| v.pos.to_array())));
+        let nr = dir_sse2(&dn, &columns(chunk, |v| v.normal.to_array()));
+        let t = dir_sse2(&dm, &columns(chunk, |v| v.tangent.to_array()));
+        let b = dir_sse2(&dm, &columns(chunk, |v| v.bitangent.to_array()));
+        out.extend(chunk.iter().enumerate().map(|(i, v)| Vertex {
+            pos: Point3::new(p[0][i], p[1][i], p[2][i]),
+            uv: v.uv,
+            normal: Vec3::new(nr[0][i], nr[1][i], nr[2][i]),
+            tangent: Vec3::new(t[0][i], t[1][i], t[2][i]),
+            bitangent: Vec3::new(b[0][i], b[1][i], b[2][i]),
+            col: v.col,
+        }));
+    }
+}
+
+//u/w, v/w and 1/w, normal, tangent, then the world position and the vertex color over w
+const ATTRS: usize = 15;
+
+//one end of a span, everything that is interpolated across it
+#[derive(Copy, Clone, Debug, Default)]
+pub struct SpanEnd([f32; ATTRS]);
+
+impl SpanEnd {
+    //uvw as in ScreenTri::tri, point and the rgb of col are divided by w
+    pub fn new(uvw: [f32; 3], normal: Vec3, tangent: Vec3, point: Vec3, col: Vec3) -> Self {
+        SpanEnd([
+            uvw[0], uvw[1], uvw[2],
+            normal.x, normal.y, normal.z,
+            tangent.x, tangent.y, tangent.z,
+            point.x, point.y, point.z,
+            col.x, col.y, col.z,
+        ])
+    }
+}
+
+//up to LANES neighbouring pixels of a span, perspective corrected
+//lanes past the end of the span or failing the depth test have their mask bit clear
+#[derive(Copy, Clone, Debug, Default)]
+pub struct SpanPixels {
+    pub mask: u32,
+    //interpolated 1/w, what the depth is computed from
+    pub inv_w: [f32; LANES],
+    //depth value to store
+    pub z: [f32; LANES],
+    pub u: [f32; LANES],
+    pub v: [f32; LANES],
+    //unit length
+    pub normal: [[f32; LANES]; 3],
+    pub tangent: [[f32; LANES]; 3],
+    pub point: [[f32; LANES]; 3],
+    //vertex color, rgb
+    pub col: [[f32; LANES]; 3],
+}
+
+impl SpanPixels {
+    #[inline]
+    pub fn normal(&self, i: usize) -> Vec3 {
+        Vec3::new(self.normal[0][i], self.normal[1][i], self.normal[2][i])
+    }
+    #[inline]
+    pub fn tangent(&self, i: usize) -> Vec3 {
+        Vec3::new(self.tangent[0][i], self.tangent[1][i], self.tangent[2][i])
+    }
+    #[inline]
+    pub fn point(&self, i: usize) -> Point3 {
+        Point3::new(self.point[0][i], self.point[1][i], self.point[2][i])
+    }
+    #[inline]
+    pub fn col(&self, i: usize) -> Rgba {
+        Rgba::rgb(self.col[0][i], self.col[1][i], self.col[2][i])
+    }
+}
+
+//one row of a triangle, step is 1 over its length in pixels
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Span {
+    pub start: SpanEnd,
+    pub end: SpanEnd,
+    pub step: f32,
+}
+
+impl Span {
+    //pixel i sits at t = (offset + i) * step, one pixel per entry of depth
+    //depth is the stored depth of those pixels, at most LANES of them
+    pub fn interpolate(&self, level: SimdLevel, offset: f32, depth: &[f32], mode: &DepthMode, out: &mut SpanPixels) {
+        let count = depth.len().min(LANES);
+        let mut stored = [0.0; LANES];
+        stored[..count].copy_from_slice(&depth[..count]);
+        let live = (1u32 << count) - 1;
+        match level.usable() {
+            #[cfg(target_arch = "x86_64")]
+            SimdLevel::Avx => unsafe { span_avx(self, offset, &stored, mode, out) },
+            #[cfg(target_arch = "x86_64")]
+            SimdLevel::Sse2 => unsafe { span_sse2(self, offset, &stored, mode, out) },
+            _ => span_scalar(self, offset, &stored, mode, out),
+        }
+        out.mask &= live;
+    }
+}
+
+fn span_scalar(span: &Span, offset: f32, stored: &[f32; LANES], mode: &DepthMode, out: &mut SpanPixels) {
+    let (s, e) = (&span.start.0, &span.end.0);
+    out.mask = 0;
+    for (i, &old) in stored.iter().enumerate() {
+        let t = (offset + i as f32) * span.step;
+        let a: [f32; ATTRS] = std::array::from_fn(|k| s[k] + (e[k] - s[k]) * t);
+        let w = a[2];
+        let z = mode.value(w);
+        if mode.test(z, old) {
+            out.mask |= 1 << i;
+        }
+        out.inv_w[i] = w;
+        out.z[i] = z;
+        out.u[i] = a[0] / w;
+        out.v[i] = a[1] / w;
+        let n = Vec3::new(a[3], a[4], a[5]).normalize();
+        for (k, &c) in n.to_array().iter().enumerate() {
+            out.normal[k][i] = c;
+            out.tangent[k][i] = a[6 + k];
+            out.point[k][i] = a[9 + k] / w;
+            out.col[k][i] = a[12 + k] / w;
+        }
+    }
+}
+
+//DepthMode::value as a scale on (far * w - 1) or (1 - near * w)
+#[inline]
+fn depth_scale(mode: &DepthMode) -> f32 {
+    let range = mode.far - mode.near;
+    if mode.reversed {
+        mode.near / range
+    } else {
+        mode.far / range
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx")]
+unsafe fn span_avx(span: &Span, offset: f32, stored: &[f32; LANES], mode: &DepthMode, out: &mut SpanPixels) {
+    let (s, e) = (&span.start.0, &span.end.0);
+    let t = _mm256_mul_ps(
+        _mm256_add_ps(_mm256_set1_ps(offset), _mm256_setr_ps(0.0, 1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0)),
+        _mm256_set1_ps(span.step),
+    );
+    let attr = |k: usize| _mm256_add_ps(_mm256_set1_ps(s[k]), _mm256_mul_ps(_mm256_set1_ps(e[k] - s[k]), t));
+    let w = attr(2);
+    let one = _mm256_set1_ps(1.0);
+    let z = if mode.reversed {
+        _mm256_sub_ps(_mm256_mul_ps(_mm256_set1_ps(mode.far), w), one)
+    } else {
+        _mm256_sub_ps(one, _mm256_mul_ps(_mm256_set1_ps(mode.near), w))
+    };
+    let z = _mm256_mul_ps(_mm256_set1_ps(depth_scale(mode)), z);
+    let old = _mm256_loadu_ps(stored.as_ptr());
+    let pass = match mode.func {
+        DepthFunc::Less => _mm256_cmp_ps(z, old, _CMP_LT_OQ),
+        DepthFunc::LessEqual => _mm256_cmp_ps(z, old, _CMP_LE_OQ),
+        DepthFunc::Greater => _mm256_cmp_ps(z, old, _CMP_GT_OQ),
+        DepthFunc::GreaterEqual => _mm256_cmp_ps(z, old, _CMP_GE_OQ),
+        DepthFunc::Always => _mm256_castsi256_ps(_mm256_set1_epi32(-1)),
+    };
+    out.mask = _mm256_movemask_ps(pass) as u32;
+    _mm256_storeu_ps(out.inv_w.as_mut_ptr(), w);
+    _mm256_storeu_ps(out.z.as_mut_ptr(), z);
+    _mm256_storeu_ps(out.u.as_mut_ptr(), _mm256_div_ps(attr(0), w));
+    _mm256_storeu_ps(out.v.as_mut_ptr(), _mm256_div_ps(attr(1), w));
+    let (nx, ny, nz) = unit_avx(attr(3), attr(4), attr(5));
+    for (k, &n) in [nx, ny, nz].iter().enumerate() {
+        _mm256_storeu_ps(out.normal[k].as_mut_ptr(), n);
+        _mm256_storeu_ps(out.tangent[k].as_mut_ptr(), attr(6 + k));
+        _mm256_storeu_ps(out.point[k].as_mut_ptr(), _mm256_div_ps(attr(9 + k), w));
+        _mm256_storeu_ps(out.col[k].as_mut_ptr(), _mm256_div_ps(attr(12 + k), w));
+    }
+}
+
+//two halves of four lanes
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "sse2")]
+unsafe fn span_sse2(span: &Span, offset: f32, stored: &[f32; LANES], mode: &DepthMode, out: &mut SpanPixels) {
+    let (s, e) = (&span.start.0, &span.end.0);
+    out.mask = 0;
+    for h in [0, 4] {
+        let first = offset + h as f32;
+        let t = _mm_mul_ps(_mm_add_ps(_mm_set1_ps(first), _mm_setr_ps(0.0, 1.0, 2.0, 3.0)), _mm_set1_ps(span.step));
+        let attr = |k: usize| _mm_add_ps(_mm_set1_ps(s[k]), _mm_mul_ps(_mm_set1_ps(e[k] - s[k]), t));
+        let w = attr(2);
+        let one = _mm_set1_ps(1.0);
+        let z = if mode.reversed {
+            _mm_sub_ps(_mm_mul_ps(_mm_set1_ps(mode.far), w), one)
+        } else {
+            _mm_sub_ps(one, _mm_mul_ps(_mm_set1_ps(mode.near), w))
+        };
+        let z = _mm_mul_ps(_mm_set1_ps(depth_scale(mode)), z);
+        let old = _mm_loadu_ps(stored[h..].as_ptr());
+        let pass = match mode.func {
+            DepthFunc::Less => _mm_cmplt_ps(z, old),
+            DepthFunc::LessEqual => _mm_cmple_ps(z, old),
+            DepthFunc::Greater => _mm_cmpgt_ps(z, old),
+            DepthFunc::GreaterEqual => _mm_cmpge_ps(z, old),
+            DepthFunc::Always => _mm_castsi128_ps(_mm_set1_epi32(-1)),
+        };
+        out.mask |= (_mm_movemask_ps(pass) as u32) << h;
+        _mm_storeu_ps(out.inv_w[h..].as_mut_ptr(), w);
+        _mm_storeu_ps(out.z[h..].as_mut_ptr(), z);
+        _mm_storeu_ps(out.u[h..].as_mut_ptr(), _mm_div_ps(attr(0), w));
+        _mm_storeu_ps(out.v[h..].as_mut_ptr(), _mm_div_ps(attr(1), w));
+        let (nx, ny, nz) = unit_sse2(attr(3), attr(4), attr(5));
+        for (k, &n) in [nx, ny, nz].iter().enumerate() {
+            _mm_storeu_ps(out.normal[k][h..].as_mut_ptr(), n);
+            _mm_storeu_ps(out.tangent[k][h..].as_mut_ptr(), attr(6 + k));
+            _mm_storeu_ps(out.point[k][h..].as_mut_ptr(), _mm_div_ps(attr(9 + k), w));
+            _mm_storeu_ps(out.col[k][h..].as_mut_ptr(), _mm_div_ps(attr(12 + k), w));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //-1..1
+    fn random(seed: &mut u32) -> f32 {
+        *seed = seed.wrapping_mul(1664525).wrapping_add(1013904223);
+        (*seed >> 8) as f32 / (1u32 << 23) as f32 - 1.0
+    }
+
+    fn vertex_bits(v: &Vertex) -> Vec<u32> {
+        let (p, n, t, b, c) = (v.pos, v.normal, v.tangent, v.bitangent, v.col);
+        [p.x, p.y, p.z, v.uv[0], v.uv[1], v.uv[2], n.x, n.y, n.z, t.x, t.y, t.z, b.x, b.y, b.z, c.r, c.g, c.b, c.a].iter().map(|f| f.to_bits()).collect()
+    }
+
+    fn pixel_bits(px: &SpanPixels) -> Vec<u32> {
+        let mut bits = vec![px.mask];
+        let rows = [px.inv_w, px.z, px.u, px.v];
+        let vecs = px.normal.iter().chain(&px.tangent).chain(&px.point).chain(&px.col);
+        for row in rows.iter().chain(vecs) {
+            bits.extend(row.iter().map(|f| f.to_bits()));
+        }
+        bits
+    }
+
+    //the wider levels this cpu can run, each has to match Scalar exactly
+    fn levels() -> Vec<SimdLevel> {
+        [SimdLevel::Sse2, SimdLevel::Avx].iter().copied().filter(|l| l.usable() == *l).collect()
+    }
+
+    #[test]
+    fn transform_vertices_matches_scalar() {
+        let mut seed = 7;
+        let mut r = || random(&mut seed);
+        let mut vs: Vec<Vertex> = (0..1001)
+            .map(|_| Vertex {
+                pos: Point3::new(r() * 10.0, r() * 10.0, r() * 10.0),
+                uv: [r(), r(), 1.0],
+                normal: Vec3::new(r(), r(), r()),
+                tangent: Vec3::new(r(), r(), r()),
+                bitangent: Vec3::new(r(), r(), r()),
+                col: Rgba::new(r(), r(), r(), r()),
+            })
+            .collect();
+        //zero length directions stay zero
+        vs[3].normal = Vec3::ZERO;
+        vs[4].tangent = Vec3::ZERO;
+        let m = Mat4::rotation_x(r()) * Mat4::rotation_y(r()) * Mat4::scaling(Vec3::new(2.0, 0.5, 1.5))
+            * Mat4::translation(Vec3::new(r(), r(), r()));
+        let p = m * Mat4::perspective(75.0, 100.0, 0.5, 640.0, 480.0);
+        for (m, n) in [(m, m), (p, m), (Mat4::IDENTITY, p)] {
+            let want: Vec<Vec<u32>> = transform_vertices(SimdLevel::Scalar, &vs, m, n).iter().map(vertex_bits).collect();
+            for level in levels() {
+                let got: Vec<Vec<u32>> = transform_vertices(level, &vs, m, n).iter().map(vertex_bits).collect();
+                assert!(got == want, "{:?}", level);
+            }
+        }
+    }
+
+    #[test]
+    fn span_matches_scalar() {
+        let mut seed = 11;
+        let mut r = || random(&mut seed);
+        for i in 0..500 {
+            let mut end = || {
+                let uvw = [r(), r(), r() + 1.5];
+                let mut v = || Vec3::new(r(), r(), r());
+                SpanEnd::new(uvw, v(), v(), v(), v())
+            };
+            let span = Span { start: end(), end: end(), step: 1.0 / (1.0 + 40.0 * r().abs()) };
+            let func = [DepthFunc::Less, DepthFunc::LessEqual, DepthFunc::Greater, DepthFunc::GreaterEqual, DepthFunc::Always][i % 5];
+            let mode = DepthMode { func, reversed: i % 2 == 0, near: 0.5, far: 250.0 };
+            let depth: Vec<f32> = (0..1 + i % LANES).map(|_| r().abs()).collect();
+            let offset = (r().abs() * 30.0).floor();
+            let mut want = SpanPixels::default();
+            span.interpolate(SimdLevel::Scalar, offset, &depth, &mode, &mut want);
+            for level in levels() {
+                let mut got = SpanPixels::default();
+                span.interpolate(level, offset, &depth, &mode, &mut got);
+                assert!(pixel_bits(&got) == pixel_bits(&want), "{:?} span {}", level, i);
+            }
+        }
+    }
+}
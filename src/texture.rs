@@ -0,0 +1,315 @@
+use crate::color::{srgb_decode, srgb_encode, Rgba};
+use crate::ops::clamp;
+use sdl2::image::LoadSurface;
+use sdl2::pixels::PixelFormatEnum;
+use sdl2::surface::Surface;
+use std::collections::HashMap;
+use std::path::Path;
+
+//what a lookup outside of 0..1 reads
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Wrap {
+    Repeat,
+    //every other repeat is flipped so the edges meet
+    Mirror,
+    Clamp,
+    Border(Rgba),
+}
+
+impl Wrap {
+    //texel index for `i` on an axis of `n` texels, none when it falls on the border
+    #[inline]
+    fn index(self, i: i32, n: i32) -> Option<i32> {
+        match self {
+            Wrap::Repeat => Some(i.rem_euclid(n)),
+            Wrap::Mirror => {
+                let m = i.rem_euclid(2 * n);
+                Some(if m < n { m } else { 2 * n - 1 - m })
+            }
+            Wrap::Clamp => Some(clamp(i, 0, n - 1)),
+            Wrap::Border(_) => if i >= 0 && i < n { Some(i) } else { None },
+        }
+    }
+}
+
+//what a texture's texels mean, decides how its smaller mip levels are averaged
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ColorSpace {
+    //srgb encoded color with straight alpha, e.g. map_Kd and map_Ke
+    Srgb,
+    //plain values, e.g. normal, roughness, metallic and alpha maps
+    Linear,
+}
+
+//one level of a mip chain, texels hold the stored 0..1 values, not decoded from srgb
+//smaller levels of Srgb textures are averaged as linear premultiplied colors and stored encoded again
+pub struct Mip {
+    pub width: usize,
+    pub height: usize,
+    pub texels: Vec<Rgba>,
+}
+
+impl Mip {
+    #[inline]
+    pub fn texel(&self, x: i32, y: i32, wrap: [Wrap; 2]) -> Rgba {
+        match (wrap[0].index(x, self.width as i32), wrap[1].index(y, self.height as i32)) {
+            (Some(x), Some(y)) => self.texels[x as usize + self.width * y as usize],
+            _ => match wrap {
+                [Wrap::Border(c), _] | [_, Wrap::Border(c)] => c,
+                _ => Rgba::BLACK,
+            },
+        }
+    }
+    #[inline]
+    pub fn nearest(&self, u: f32, v: f32, wrap: [Wrap; 2]) -> Rgba {
+        self.texel((u * self.width as f32).floor() as i32, (v * self.height as f32).floor() as i32, wrap)
+    }
+    #[inline]
+    pub fn bilinear(&self, u: f32, v: f32, wrap: [Wrap; 2]) -> Rgba {
+        let x = u * self.width as f32 - 0.5;
+        let y = v * self.height as f32 - 0.5;
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = (x - x0, y - y0);
+        let (x0, y0) = (x0 as i32, y0 as i32);
+        let top = self.texel(x0, y0, wrap).lerp(self.texel(x0 + 1, y0, wrap), fx);
+        let bottom = self.texel(x0, y0 + 1, wrap).lerp(self.texel(x0 + 1, y0 + 1, wrap), fx);
+        top.lerp(bottom, fy)
+    }
+    //2x2 box filter, on odd sizes the last texel also takes in the row or column left over
+    //for Srgb, transparent texels don't bleed their color and dark ones don't win over bright ones
+    fn half(&self, space: ColorSpace) -> Mip {
+        let width = (self.width / 2).max(1);
+        let height = (self.height / 2).max(1);
+        //source texels that go into output texel i of n
+        let span = |i: usize, n: usize, len: usize| 2 * i..if i + 1 == n { len } else { 2 * i + 2 };
+        let mut texels = Vec::with_capacity(width * height);
+        for y in 0..height {
+            for x in 0..width {
+                let (xs, ys) = (span(x, width, self.width), span(y, height, self.height));
+                let count = (xs.len() * ys.len()) as f32;
+                let mut sum = Rgba::new(0.0, 0.0, 0.0, 0.0);
+                for sy in ys {
+                    for sx in xs.clone() {
+                        let t = self.texels[sx + self.width * sy];
+                        sum += match space {
+                            ColorSpace::Srgb => t.map(srgb_decode).premultiply(),
+                            ColorSpace::Linear => t,
+                        };
+                    }
+                }
+                let avg = sum * (1.0 / count);
+                texels.push(match space {
+                    ColorSpace::Srgb => avg.unpremultiply().map(srgb_encode),
+                    ColorSpace::Linear => avg,
+                });
+            }
+        }
+        Mip { width, height, texels }
+    }
+}
+
+//decoded copy of an image, owns its pixels so it can be shared freely once loaded
+//only the mips are kept, the bytes it was made from are dropped
+pub struct Texture {
+    pub width: usize,
+    pub height: usize,
+    //level 0 is full size, each next one half of it down to 1x1
+    pub mips: Vec<Mip>,
+    //along u and v
+    pub wrap: [Wrap; 2],
+    //what the mips were built for
+    pub space: ColorSpace,
+}
+
+impl Texture {
+    //data is rows of `pitch` bytes of `channels` bytes per pixel: grey, grey and alpha, rgb or rgba
+    pub fn new(width: usize, height: usize, pitch: usize, channels: usize, data: Vec<u8>, space: ColorSpace) -> Self {
+        let mut texels = Vec::with_capacity(width * height);
+        for y in 0..height {
+            for x in 0..width {
+                let i = x * channels + y * pitch;
+                let b = |k: usize| data[i + k] as f32 / 255.0;
+                texels.push(match channels {
+                    1 => Rgba::grey(b(0)),
+                    2 => Rgba::new(b(0), b(0), b(0), b(1)),
+                    3 => Rgba::rgb(b(0), b(1), b(2)),
+                    _ => Rgba::new(b(0), b(1), b(2), b(3)),
+                });
+            }
+        }
+        let mut mips = vec![Mip { width, height, texels }];
+        while let Some(m) = mips.last().filter(|m| m.width > 1 || m.height > 1) {
+            let next = m.half(space);
+            mips.push(next);
+        }
+        Texture {
+            width,
+            height,
+            mips,
+            wrap: [Wrap::Repeat; 2],
+            space,
+        }
+    }
+    //whatever the file's pixel format, paletted and greyscale included, it is converted to rgba bytes
+    pub fn from_file(path: &str, space: ColorSpace) -> Result<Self, String> {
+        let surf: Surface = LoadSurface::from_file(Path::new(path))?;
+        let surf = surf.convert_format(PixelFormatEnum::RGBA32)?;
+        Ok(Texture::new(
+            surf.width() as usize,
+            surf.height() as usize,
+            surf.pitch() as usize,
+            4,
+            surf.with_lock(|b| b.to_vec()),
+            space,
+        ))
+    }
+    //mip level for a pixel whose uv changes by (dudx, dvdx) and (dudy, dvdy) to its neighbours
+    #[inline]
+    pub fn lod(&self, dudx: f32, dvdx: f32, dudy: f32, dvdy: f32) -> f32 {
+        let (w, h) = (self.width as f32, self.height as f32);
+        let dx = (dudx * w).powi(2) + (dvdx * h).powi(2);
+        let dy = (dudy * w).powi(2) + (dvdy * h).powi(2);
+        0.5 * dx.max(dy).max(1e-12).log2()
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Filter {
+    //closest texel of the full size image
+    Nearest,
+    //blend of four texels on the closest mip level
+    Bilinear,
+    //bilinear on the two mip levels around the lod, blended
+    Trilinear,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Sampler {
+    pub filter: Filter,
+    //added to every lod, positive is blurrier
+    pub lod_bias: f32,
+}
+
+impl Default for Sampler {
+    fn default() -> Self {
+        Sampler {
+            filter: Filter::Trilinear,
+            lod_bias: 0.0,
+        }
+    }
+}
+
+impl Sampler {
+    //u and v run 0..1 across the texture, outside is up to the texture's wrap
+    #[inline]
+    pub fn sample(&self, tex: &Texture, u: f32, v: f32, lod: f32) -> Rgba {
+        let last = (tex.mips.len() - 1) as f32;
+        let lod = clamp(lod + self.lod_bias, 0.0, last);
+        let wrap = tex.wrap;
+        match self.filter {
+            Filter::Nearest => tex.mips[0].nearest(u, v, wrap),
+            Filter::Bilinear => tex.mips[lod.round() as usize].bilinear(u, v, wrap),
+            Filter::Trilinear => {
+                let l0 = lod.floor();
+                let a = tex.mips[l0 as usize].bilinear(u, v, wrap);
+                if lod - l0 <= 0.0 {
+                    return a;
+                }
+                a.lerp(tex.mips[l0 as usize + 1].bilinear(u, v, wrap), lod - l0)
+            }
+        }
+    }
+}
+
+//textures keyed by path, meshes that name the same file share one decoded copy
+pub struct TextureManager {
+    textures: HashMap<String, Texture>,
+    white: Texture,
+}
+impl Default for TextureManager {
+    fn default() -> Self {
+        TextureManager::new()
+    }
+}
+impl TextureManager {
+    pub fn new() -> Self {
+        TextureManager {
+            textures: HashMap::new(),
+            white: Texture::new(1, 1, 4, 4, vec![255; 4], ColorSpace::Srgb),
+        }
+    }
+    //a texture that was made in memory rather than loaded, later loads of `name` find it
+    pub fn insert(&mut self, name: &str, tex: Texture) -> Option<Texture> {
+        self.textures.insert(name.to_string(), tex)
+    }
+    //single white texel, what meshes without a texture are drawn with
+    pub fn white(&self) -> &Texture {
+        &self.white
+    }
+    //decodes the file the first time it is asked for, later loads get that copy whatever their `space`
+    pub fn load(&mut self, path: &str, space: ColorSpace) -> Result<&Texture, String> {
+        if !self.textures.contains_key(path) {
+            self.textures.insert(path.to_string(), Texture::from_file(path, space)?);
+        }
+        Ok(&self.textures[path])
+    }
+    pub fn get(&self, path: &str) -> Option<&Texture> {
+        self.textures.get(path)
+    }
+    //e.g. to change the wrap of a loaded texture
+    pub fn get_mut(&mut self, path: &str) -> Option<&mut Texture> {
+        self.textures.get_mut(path)
+    }
+    pub fn unload(&mut self, path: &str) -> Option<Texture> {
+        self.textures.remove(path)
+    }
+    //decodes the file again, e.g. after it changed on disk, in the color space it was loaded with
+    pub fn reload(&mut self, path: &str) -> Result<&Texture, String> {
+        let space = self.textures.get(path).map_or(ColorSpace::Srgb, |t| t.space);
+        self.textures.insert(path.to_string(), Texture::from_file(path, space)?);
+        Ok(&self.textures[path])
+    }
+    pub fn clear(&mut self) {
+        self.textures.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mips_average_linear_premultiplied() {
+        //opaque white next to transparent red, the red must not tint the average
+        let tex = Texture::new(2, 1, 8, 4, vec![255, 255, 255, 255, 255, 0, 0, 0], ColorSpace::Srgb);
+        let m = tex.mips[1].texels[0];
+        assert!([m.r, m.g, m.b].iter().all(|c| (c - 1.0).abs() < 1e-6), "{:?}", m);
+        assert!((m.a - 0.5).abs() < 1e-6);
+        //black and white average to half the light, which srgb encodes well above 0.5
+        let tex = Texture::new(2, 1, 6, 3, vec![0, 0, 0, 255, 255, 255], ColorSpace::Srgb);
+        let m = tex.mips[1].texels[0];
+        assert!((m.r - srgb_encode(0.5)).abs() < 1e-6 && m.r > 0.7, "{}", m.r);
+    }
+
+    #[test]
+    fn linear_mips_average_as_stored() {
+        //normals along +x and +z, their average must stay between them
+        let tex = Texture::new(2, 1, 6, 3, vec![255, 128, 128, 128, 128, 255], ColorSpace::Linear);
+        let m = tex.mips[1].texels[0];
+        let want = (255.0 + 128.0) / 2.0 / 255.0;
+        assert!((m.r - want).abs() < 1e-6 && (m.b - want).abs() < 1e-6, "{:?}", m);
+        //alpha does not scale the other channels
+        let tex = Texture::new(2, 1, 4, 2, vec![255, 255, 255, 0], ColorSpace::Linear);
+        let m = tex.mips[1].texels[0];
+        assert!((m.r - 1.0).abs() < 1e-6 && (m.a - 0.5).abs() < 1e-6, "{:?}", m);
+    }
+
+    #[test]
+    fn odd_sizes_fold_in_the_last_texels() {
+        let tex = Texture::new(3, 1, 3, 1, vec![0, 0, 255], ColorSpace::Linear);
+        assert_eq!(tex.mips.len(), 2);
+        assert!((tex.mips[1].texels[0].r - 1.0 / 3.0).abs() < 1e-6);
+        let tex = Texture::new(3, 3, 3, 1, vec![0, 0, 0, 0, 0, 0, 0, 0, 255], ColorSpace::Linear);
+        assert!((tex.mips[1].texels[0].r - 1.0 / 9.0).abs() < 1e-6);
+    }
+}
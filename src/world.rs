@@ -1,394 +1,203 @@
-use std::fs::{read_to_string, File};
-use std::io::{BufRead, BufReader, Read};
+use std::path::Path;
 use arrayvec;
 use crate::Tri3d;
-use crate::Vec3;
-use sdl2::pixels::Color;
-use sdl2::surface::{Surface, SurfaceContext, SurfaceRef};
+use crate::ops::{clamp, Mat4, Point3, Quat, Transform, Vec3};
+use std::f32::consts::PI;
+use crate::obj::{load_obj, ObjError};
+use crate::material::Material;
+use crate::mesh::{IndexedMesh, Vertex};
+use crate::texture::TextureManager;
+use crate::color::Rgba;
+use crate::scene::{Instance, Scene};
 pub struct Camera {
     pub fov: f32,
-    pub pos: [f32; 4],
-    pub dir: [f32; 4],
-    pub vel: [f32; 4],
-    pub rot_vel: [f32; 4],
+    pub pos: Point3,
+    pub dir: Vec3,
+    pub vel: Vec3,
+    pub rot_vel: Vec3,
     pub clip_distance: f32,
     pub render_distance: f32,
     pub window_height: f32,
     pub window_width: f32,
 }
 
+impl Camera {
+    //yaw about up, then pitch towards it, stopping short of looking straight up or down
+    pub fn turn(&mut self, yaw: f32, pitch: f32, up: Vec3) {
+        let up = up.normalize();
+        let dir = Quat::from_axis_angle(up, yaw).rotate(self.dir);
+        //angle from up, pitching up makes it smaller
+        let from_up = clamp(dir.dot(up) / dir.length(), -1.0, 1.0).acos();
+        let pitch = clamp(pitch, from_up - (PI - 0.01), from_up - 0.01);
+        self.dir = Quat::from_axis_angle(dir.cross(up), pitch).rotate(dir);
+    }
+}
+
 pub struct Engine {
     pub camera: Camera,
     pub objects: Vec<Mesh>,
-    pub depth_buffer: Vec<f32>,
-    pub transparency_buffer: Vec<(f32, Color)>,
     pub lights: Vec<crate::light::Light>,
-    pub ambient: Color,
-}
-pub fn matrix3d_perspective(
-    fov: f32,
-    render_distance: f32,
-    clip_distance: f32,
-    window_width: f32,
-    window_height: f32,
-) -> [[f32; 4]; 4] {
-    let t = (fov.to_radians() * 0.5).tan();
-    let zratio = render_distance / (render_distance - clip_distance);
-    [
-        [-window_height / (t * window_width), 0.0, 0.0, 0.0],
-        [0.0, -1.0 / t, 0.0, 0.0],
-        [0.0, 0.0, zratio, 1.0],
-        [0.0, 0.0, -clip_distance * zratio, 0.0],
-    ]
-}
-pub fn matrix3d_ortho(r: f32, t: f32, n: f32, f: f32) -> [[f32; 4]; 4] {
-    [
-        [-1.0 / r, 0.0, 0.0, 0.0],
-        [0.0, -1.0 / t, 0.0, 0.0],
-        [0.0, 0.0, 1.0 / (f - n), 0.0],
-        [0.0, 0.0, 0.0, 1.0],
-    ]
+    pub ambient: Rgba,
+    pub textures: TextureManager,
+    //placement of the meshes in objects
+    pub scene: Scene,
+    //whether meshes no node points at are drawn too, with the identity matrix, where their vertices are
+    //on by default so meshes pushed to objects show up without a scene
+    pub draw_unplaced: bool,
 }
 impl Engine {
-    pub fn x_rot(angle: f32) -> [[f32; 4]; 4] {
-        [
-            [1.0, 0.0, 0.0, 0.0],
-            [0.0, angle.cos(), angle.sin(), 0.0],
-            [0.0, -angle.sin(), angle.cos(), 0.0],
-            [0.0, 0.0, 0.0, 1.0],
-        ]
-    }
-    pub fn y_rot(angle: f32) -> [[f32; 4]; 4] {
-        [
-            [angle.cos(), 0.0, -angle.sin(), 0.0],
-            [0.0, 1.0, 0.0, 0.0],
-            [angle.sin(), 0.0, angle.cos(), 0.0],
-            [0.0, 0.0, 0.0, 1.0],
-        ]
-    }
-    pub fn z_rot(angle: f32) -> [[f32; 4]; 4] {
-        [
-            [angle.cos(), -angle.sin(), 0.0, 0.0],
-            [angle.sin(), angle.cos(), 0.0, 0.0],
-            [0.0, 0.0, 1.0, 0.0],
-            [0.0, 0.0, 0.0, 1.0],
-        ]
-    }
-    pub fn xyz_rot(x:f32, y:f32, z:f32) -> [[f32;4];4]{
-        let zcos = z.cos();
-        let zsin = z.sin();
-        let xcos = x.cos();
-        let xsin = x.sin();
-        let ycos = y.cos();
-        let ysin = y.sin();
-        [
-            [ycos*zcos, -zsin*ycos, ysin, 0.0],
-            [zcos*xsin*ysin+zsin*xcos, zcos*xcos-zsin*xsin*ysin, -xsin*ycos, 0.0],
-            [zsin*xsin-zcos*xcos*ysin, zsin*xcos*ysin+zcos*xsin, xcos*ycos, 0.0],
-            [0.0, 0.0, 0.0, 1.0]
-        ]
+    pub fn new(camera: Camera) -> Self {
+        Engine {
+            camera,
+            objects: Vec::new(),
+            lights: Vec::new(),
+            ambient: Rgba::BLACK,
+            textures: TextureManager::new(),
+            scene: Scene::new(),
+            draw_unplaced: true,
+        }
     }
-    pub fn sort_objs(&mut self) {
-        let cpos = self.camera.pos;
-        self.objects.sort_by(|a, b| {
-            a.center()
-                .subtract(cpos)
-                .magnitude()
-                .partial_cmp(&b.center().subtract(cpos).magnitude())
-                .unwrap()
-        })
+    //every mesh to draw with its world and normal matrix, as of the last Scene::update
+    //the meshes the scene places, then with draw_unplaced the rest
+    pub fn instances(&self) -> Vec<Instance<'_>> {
+        let mut placed = vec![false; self.objects.len()];
+        let mut out = Vec::new();
+        for (id, node) in self.scene.nodes().iter().enumerate() {
+            if let Some(i) = node.mesh.filter(|&i| i < self.objects.len()) {
+                placed[i] = true;
+                out.push(Instance { mesh: &self.objects[i], world: self.scene.world(id), normal: self.scene.world_normal(id) });
+            }
+        }
+        if self.draw_unplaced {
+            for (mesh, _) in self.objects.iter().zip(&placed).filter(|(_, &p)| !p) {
+                out.push(Instance { mesh, world: Mat4::IDENTITY, normal: Mat4::IDENTITY });
+            }
+        }
+        out
     }
 }
 pub struct Mesh {
-    pub tris: Vec<Tri3d>,
-    pub vel: [f32; 4],
-    pub rot_vel: [f32; 4],
+    pub geometry: IndexedMesh,
+    pub vel: Vec3,
+    pub rot_vel: Vec3,
+    //key into Engine::textures, empty or missing draws white
     pub tex: String,
+    pub materials: Vec<Material>,
+    pub depth_write: bool,
 }
 
 impl Mesh {
+    //from a triangle soup, see IndexedMesh::from_tris
     pub fn new(tris: Vec<Tri3d>, tex: String) -> Self {
-        Mesh {
-            tris,
-            vel: [0.0, 0.0, 0.0, 0.0],
-            rot_vel: [0.0, 0.0, 0.0, 0.0],
-            tex,
-        }
+        Mesh::from_geometry(IndexedMesh::from_tris(&tris), tex)
     }
-    #[inline]
-    pub fn center(&self) -> [f32; 4] {
-        let mut c = [0.0, 0.0, 0.0, 1.0];
-        let n = 1.0/self.tris.len() as f32;
-        for tri in &self.tris {
-            c = c.add(tri.center());
-        }
-        c.scale([n, n, n, 1.0])
-    }
-
-    pub fn load_obj_file(file_path: String, tex: String, col: Color, rfl: f32, trs: f32) -> Self {
-        let file = File::open(file_path).unwrap();
-        let reader = BufReader::new(file);
-        let mut ts: Vec<Tri3d> = Vec::new();
-        let mut t_n: Vec<[f32; 4]> = Vec::new();
-        let mut points: Vec<[f32; 4]> = Vec::new();
-        let mut t_c: Vec<[f32; 3]> = Vec::new();
-        let obj_key: [&str; 4] = ["v", "f", "vt", "vn"];
-
-        for line in reader.lines() {
-            let ln = Box::leak(line.unwrap().into_boxed_str());
-            let vals: Vec<&str> = ln.split_whitespace().collect();
-            if !vals.is_empty() {
-                if *vals[0] == *obj_key[0] {
-                    points.push([
-                        vals[1].parse::<f32>().unwrap(),
-                        vals[2].parse::<f32>().unwrap(),
-                        vals[3].parse::<f32>().unwrap(),
-                        1.0,
-                    ]);
-                } else if *vals[0] == *obj_key[1] {
-                    let p1: Vec<&str> = vals[1].split('/').collect();
-                    let p2: Vec<&str> = vals[2].split('/').collect();
-                    let p3: Vec<&str> = vals[3].split('/').collect();
-                    if p1.len() == 2 {
-                        ts.push(Tri3d::new(
-                            [
-                                points[p1[0].parse::<usize>().unwrap() - 1],
-                                points[p2[0].parse::<usize>().unwrap() - 1],
-                                points[p3[0].parse::<usize>().unwrap() - 1],
-                            ],
-                            [
-                                t_c[p1[1].parse::<usize>().unwrap() - 1],
-                                t_c[p2[1].parse::<usize>().unwrap() - 1],
-                                t_c[p3[1].parse::<usize>().unwrap() - 1],
-                            ],
-                            [
-                                [0.0, 0.0, 0.0, 1.0],
-                                [0.0, 0.0, 0.0, 1.0],
-                                [0.0, 0.0, 0.0, 1.0],
-                            ],
-                            col,
-                            rfl,
-                            trs,
-                        ));
-                    } else if p1.len() == 1 {
-                        ts.push(Tri3d::new(
-                            [
-                                points[vals[1].parse::<usize>().unwrap() - 1],
-                                points[vals[2].parse::<usize>().unwrap() - 1],
-                                points[vals[3].parse::<usize>().unwrap() - 1],
-                            ],
-                            [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [1.0, 1.0, 0.0]],
-                            [
-                                [0.0, 0.0, 0.0, 1.0],
-                                [0.0, 0.0, 0.0, 1.0],
-                                [0.0, 0.0, 0.0, 1.0],
-                            ],
-                            col,
-                            rfl,
-                            trs,
-                        ));
-                    } else if p1.len() == 3 {
-                        ts.push(Tri3d::new(
-                            [
-                                points[p1[0].parse::<usize>().unwrap() - 1],
-                                points[p2[0].parse::<usize>().unwrap() - 1],
-                                points[p3[0].parse::<usize>().unwrap() - 1],
-                            ],
-                            [
-                                t_c[p1[1].parse::<usize>().unwrap() - 1],
-                                t_c[p2[1].parse::<usize>().unwrap() - 1],
-                                t_c[p3[1].parse::<usize>().unwrap() - 1],
-                            ],
-                            [
-                                t_n[p1[2].parse::<usize>().unwrap() - 1],
-                                t_n[p2[2].parse::<usize>().unwrap() - 1],
-                                t_n[p3[2].parse::<usize>().unwrap() - 1],
-                            ],
-                            col,
-                            rfl,
-                            trs,
-                        ));
-                    }
-                } else if *vals[0] == *obj_key[2] {
-                    t_c.push([
-                        1.0 - vals[1].parse::<f32>().unwrap(),
-                        1.0 - vals[2].parse::<f32>().unwrap(),
-                        1.0,
-                    ]);
-                } else if *vals[0] == *obj_key[3] {
-                    t_n.push(
-                        [
-                            vals[1].parse::<f32>().unwrap(),
-                            vals[2].parse::<f32>().unwrap(),
-                            vals[3].parse::<f32>().unwrap(),
-                            1.0,
-                        ]
-                        .normalize(),
-                    )
-                }
-            }
-        }
+    pub fn from_geometry(geometry: IndexedMesh, tex: String) -> Self {
         Mesh {
-            tris: ts,
-            vel: [0.0, 0.0, 0.0, 0.0],
-            rot_vel: [0.0, 0.0, 0.0, 0.0],
+            geometry,
+            vel: Vec3::ZERO,
+            rot_vel: Vec3::ZERO,
             tex,
+            materials: Vec::new(),
+            depth_write: true,
         }
     }
-    pub fn translate(&self, t: [f32; 4]) -> Self {
-        let mut s = Vec::new();
-        for i in &self.tris {
-            s.push(i.translate(t));
-        }
+    fn with_vertices(&self, f: impl Fn(&Vertex) -> Vertex) -> Self {
         Mesh {
-            tris: s,
+            geometry: self.geometry.map_vertices(f),
             vel: self.vel,
             rot_vel: self.rot_vel,
-            tex: self.tex.as_str().to_string(),
+            tex: self.tex.clone(),
+            materials: self.materials.clone(),
+            depth_write: self.depth_write,
         }
     }
-    pub fn scale(&self, t: [f32; 4]) -> Self {
-        let mut s = Vec::new();
-        for i in &self.tris {
-            s.push(i.scale(t));
-        }
-        Mesh {
-            tris: s,
-            vel: self.vel,
-            rot_vel: self.rot_vel,
-            tex: self.tex.as_str().to_string(),
+    #[inline]
+    pub fn center(&self) -> Point3 {
+        let mut c = Vec3::ZERO;
+        let n = 1.0/self.geometry.len() as f32;
+        for tri in self.geometry.tris() {
+            c += tri.center().to_vec();
         }
+        Point3::from_vec(c * n)
     }
-    pub fn rotate_point(&self, deg: [f32; 4], point: [f32; 4]) -> Self {
-        let mut ts = Vec::new();
-        for i in 0..ts.len() {
-            ts.push(self.tris[i].translate(point.negative()));
-            if deg[2] != 0.0 {
-                ts[i] = ts[i].multiply_mat(Engine::z_rot(deg[2]));
-            }
-            if deg[1] != 0.0 {
-                ts[i] = ts[i].multiply_mat(Engine::y_rot(deg[1]));
-            }
-            if deg[0] != 0.0 {
-                ts[i] = ts[i].multiply_mat(Engine::x_rot(deg[0]));
-            }
-            ts[i] = ts[i].translate(point);
-        }
-        Mesh {
-            tris: ts,
-            vel: self.vel,
-            rot_vel: self.rot_vel,
-            tex: self.tex.as_str().to_string(),
-        }
+
+    pub fn load_obj_file(file_path: String, tex: String, col: Rgba, rfl: f32, trs: f32) -> Result<Self, ObjError> {
+        let (geometry, materials) = load_obj(Path::new(&file_path), col, rfl, trs)?;
+        Ok(Mesh {
+            materials,
+            ..Mesh::from_geometry(geometry, tex)
+        })
+    }
+    pub fn translate(&self, t: Vec3) -> Self {
+        self.with_vertices(|v| v.translate(t))
+    }
+    pub fn scale(&self, t: Vec3) -> Self {
+        self.with_vertices(|v| v.scale(t))
+    }
+    pub fn rotate_point(&self, deg: Vec3, point: Point3) -> Self {
+        self.upd(Vec3::ZERO, deg, point)
+    }
+    //bakes t into a copy of the vertices, nodes in the scene do the same without copying
+    pub fn transform(&self, t: &Transform) -> Self {
+        let (m, n) = (t.matrix(), t.normal_matrix());
+        self.with_vertices(|v| v.transform(m, n))
     }
     #[inline]
     pub fn upd(
         &self,
-        trans: [f32; 4],
-        rot: [f32; 4],
-        rot_point: [f32; 4],
+        trans: Vec3,
+        rot: Vec3,
+        rot_point: Point3,
     ) -> Self {
-
-        let mut ts = vec![];
-        for i in &self.tris {
-            ts.push(i.upd(trans, rot, rot_point))
-        }
-        Mesh {
-            tris: ts,
-            vel: self.vel,
-            rot_vel: self.rot_vel,
-            tex: self.tex.as_str().to_string(),
-        }
+        let m = Quat::from_euler(rot.x, rot.y, rot.z).to_matrix();
+        self.with_vertices(|v| v.translate(-rot_point.to_vec()).transform(m, m).translate(rot_point.to_vec()).translate(trans))
     }
-    pub fn multiply_mat(&self, mat: [[f32; 4]; 4]) -> Self {
-        let mut ts = vec![];
-        for i in &self.tris {
-            ts.push(i.multiply_mat(mat));
-        }
-        Mesh {
-            tris: ts,
-            vel: self.vel,
-            rot_vel: self.rot_vel,
-            tex: self.tex.as_str().to_string(),
-        }
+    pub fn multiply_mat(&self, mat: Mat4) -> Self {
+        self.with_vertices(|v| v.transform(mat, mat))
     }
 }
 
-pub fn point_at(pos: [f32; 4], target: [f32; 4], up: [f32; 4]) -> [[f32; 4]; 4] {
-    let forward = target.subtract(pos).normalize();
-
-    let up = up
-        .subtract(forward.scale_c(up.dot_product(forward)))
-        .normalize();
-
-    let right = up.cross_product(forward).normalize();
-
-    [
-        [right[0], right[1], right[2], 0.0],
-        [up[0], up[1], up[2], 0.0],
-        [forward[0], forward[1], forward[2], 0.0],
-        pos,
-    ]
-}
-
-pub fn quick_inv(m: [[f32; 4]; 4]) -> [[f32; 4]; 4] {
-    //    mat4x4 matrix;
-    //    matrix.m[0][0] = m.m[0][0]; matrix.m[0][1] = m.m[1][0]; matrix.m[0][2] = m.m[2][0]; matrix.m[0][3] = 0.0f;
-    //    matrix.m[1][0] = m.m[0][1]; matrix.m[1][1] = m.m[1][1]; matrix.m[1][2] = m.m[2][1]; matrix.m[1][3] = 0.0f;
-    //    matrix.m[2][0] = m.m[0][2]; matrix.m[2][1] = m.m[1][2]; matrix.m[2][2] = m.m[2][2]; matrix.m[2][3] = 0.0f;
-    //    matrix.m[3][0] = -(m.m[3][0] * matrix.m[0][0] + m.m[3][1] * matrix.m[1][0] + m.m[3][2] * matrix.m[2][0]);
-    //    matrix.m[3][1] = -(m.m[3][0] * matrix.m[0][1] + m.m[3][1] * matrix.m[1][1] + m.m[3][2] * matrix.m[2][1]);
-    //    matrix.m[3][2] = -(m.m[3][0] * matrix.m[0][2] + m.m[3][1] * matrix.m[1][2] + m.m[3][2] * matrix.m[2][2]);
-    //    matrix.m[3][3] = 1.0f;
-    //yes i copied olc what are you gonna do abt it
-    [
-        [m[0][0], m[1][0], m[2][0], 0.0],
-        [m[0][1], m[1][1], m[2][1], 0.0],
-        [m[0][2], m[1][2], m[2][2], 0.0],
-        [
-            -(m[3][0] * m[0][0] + m[3][1] * m[0][1] + m[3][2] * m[0][2]),
-            -(m[3][0] * m[1][0] + m[3][1] * m[1][1] + m[3][2] * m[1][2]),
-            -(m[3][0] * m[2][0] + m[3][1] * m[2][1] + m[3][2] * m[2][2]),
-            1.0,
-        ],
-    ]
-}
-
 pub fn vec_intersect_plane(
-    plane_p: [f32; 4],
-    plane_n: [f32; 4],
-    line_s: [f32; 4],
-    line_e: [f32; 4],
-) -> ([f32; 4], f32) {
+    plane_p: Point3,
+    plane_n: Vec3,
+    line_s: Point3,
+    line_e: Point3,
+) -> (Point3, f32) {
     let plane_n = plane_n.normalize();
-    let plane_d = -plane_p.dot_product(plane_n);
-    let ad = line_s.dot_product(plane_n);
-    let bd = line_e.dot_product(plane_n);
+    let plane_d = -plane_p.to_vec().dot(plane_n);
+    let ad = line_s.to_vec().dot(plane_n);
+    let bd = line_e.to_vec().dot(plane_n);
     let t = (-plane_d - ad) / (bd - ad);
-    (line_s.add(line_e.subtract(line_s).scale_c(t)), t)
+    (line_s.lerp(line_e, t), t)
 }
 
 pub fn clip_tri(
-    mat3d: [[f32;4];4],
-    plane_p: [f32; 4],
-    plane_n: [f32; 4],
+    plane_p: Point3,
+    plane_n: Vec3,
     in_tri: Tri3d,
     out_tris: &mut [Tri3d; 2],
 ) -> usize {
-    let plane_n = plane_n;
-
-    let dist = |p: [f32; 4]| -> f32 { p.dot_product(plane_n) - plane_n.dot_product(plane_p) };
+    let dist = |p: Point3| -> f32 { (p - plane_p).dot(plane_n) };
     
     
-    let mut in_points : arrayvec::ArrayVec<[f32;4], 3> = arrayvec::ArrayVec::new();
+    let mut in_points : arrayvec::ArrayVec<Point3, 3> = arrayvec::ArrayVec::new();
 
-    let mut out_points : arrayvec::ArrayVec<[f32;4], 3> = arrayvec::ArrayVec::new();
+    let mut out_points : arrayvec::ArrayVec<Point3, 3> = arrayvec::ArrayVec::new();
 
     let mut in_uvs : arrayvec::ArrayVec<[f32;3], 3> = arrayvec::ArrayVec::new();
     let mut out_uvs : arrayvec::ArrayVec<[f32;3], 3> = arrayvec::ArrayVec::new();
 
-    let mut in_ns : arrayvec::ArrayVec<[f32;4], 3> = arrayvec::ArrayVec::new();
-    let mut out_ns : arrayvec::ArrayVec<[f32;4], 3> = arrayvec::ArrayVec::new();
+    let mut in_ns : arrayvec::ArrayVec<Vec3, 3> = arrayvec::ArrayVec::new();
+    let mut out_ns : arrayvec::ArrayVec<Vec3, 3> = arrayvec::ArrayVec::new();
+
+    let mut in_ts : arrayvec::ArrayVec<Vec3, 3> = arrayvec::ArrayVec::new();
+    let mut out_ts : arrayvec::ArrayVec<Vec3, 3> = arrayvec::ArrayVec::new();
+
+    let mut in_bs : arrayvec::ArrayVec<Vec3, 3> = arrayvec::ArrayVec::new();
+    let mut out_bs : arrayvec::ArrayVec<Vec3, 3> = arrayvec::ArrayVec::new();
+
+    let mut in_cols : arrayvec::ArrayVec<Rgba, 3> = arrayvec::ArrayVec::new();
+    let mut out_cols : arrayvec::ArrayVec<Rgba, 3> = arrayvec::ArrayVec::new();
 
     let d0 = dist(in_tri.ps[0]);
     let d1 = dist(in_tri.ps[1]);
@@ -399,22 +208,34 @@ pub fn clip_tri(
 
         in_uvs.push(in_tri.uvs[0]);
         in_ns.push(in_tri.ns[0]);
+        in_ts.push(in_tri.ts[0]);
+        in_bs.push(in_tri.bs[0]);
+        in_cols.push(in_tri.cols[0]);
     } else {
         out_points.push(in_tri.ps[0]);
 
         out_uvs.push(in_tri.uvs[0]);
         out_ns.push(in_tri.ns[0]);
+        out_ts.push(in_tri.ts[0]);
+        out_bs.push(in_tri.bs[0]);
+        out_cols.push(in_tri.cols[0]);
     }
 
     if d1 >= 0.0 {
         in_points.push(in_tri.ps[1]);
         in_uvs.push(in_tri.uvs[1]);
         in_ns.push(in_tri.ns[1]);
+        in_ts.push(in_tri.ts[1]);
+        in_bs.push(in_tri.bs[1]);
+        in_cols.push(in_tri.cols[1]);
     } else {
         out_points.push(in_tri.ps[1]);
 
         out_uvs.push(in_tri.uvs[1]);
         out_ns.push(in_tri.ns[1]);
+        out_ts.push(in_tri.ts[1]);
+        out_bs.push(in_tri.bs[1]);
+        out_cols.push(in_tri.cols[1]);
     }
 
     if d2 >= 0.0 {
@@ -422,11 +243,17 @@ pub fn clip_tri(
 
         in_uvs.push(in_tri.uvs[2]);
         in_ns.push(in_tri.ns[2]);
+        in_ts.push(in_tri.ts[2]);
+        in_bs.push(in_tri.bs[2]);
+        in_cols.push(in_tri.cols[2]);
     } else {
         out_points.push(in_tri.ps[2]);
 
         out_uvs.push(in_tri.uvs[2]);
         out_ns.push(in_tri.ns[2]);
+        out_ts.push(in_tri.ts[2]);
+        out_bs.push(in_tri.bs[2]);
+        out_cols.push(in_tri.cols[2]);
     }
 
     if in_points.len() == 3 {
@@ -460,8 +287,20 @@ pub fn clip_tri(
         ];
 
         out_tris[0].ns[0] = in_ns[0];
-        out_tris[0].ns[1] = out_ns[0].subtract(in_ns[0]).scale_c(tab).add(in_ns[0]);
-        out_tris[0].ns[2] = out_ns[1].subtract(in_ns[0]).scale_c(tac).add(in_ns[0]);
+        out_tris[0].ns[1] = in_ns[0].lerp(out_ns[0], tab);
+        out_tris[0].ns[2] = in_ns[0].lerp(out_ns[1], tac);
+
+        out_tris[0].ts[0] = in_ts[0];
+        out_tris[0].ts[1] = in_ts[0].lerp(out_ts[0], tab);
+        out_tris[0].ts[2] = in_ts[0].lerp(out_ts[1], tac);
+
+        out_tris[0].bs[0] = in_bs[0];
+        out_tris[0].bs[1] = in_bs[0].lerp(out_bs[0], tab);
+        out_tris[0].bs[2] = in_bs[0].lerp(out_bs[1], tac);
+
+        out_tris[0].cols[0] = in_cols[0];
+        out_tris[0].cols[1] = in_cols[0].lerp(out_cols[0], tab);
+        out_tris[0].cols[2] = in_cols[0].lerp(out_cols[1], tac);
 
         //out_tris[0].col = Color::RED;
         return 1;
@@ -490,7 +329,19 @@ pub fn clip_tri(
 
         out_tris[0].ns[0] = in_ns[0];
         out_tris[0].ns[1] = in_ns[1];
-        out_tris[0].ns[2] = out_ns[0].subtract(in_ns[0]).scale_c(tac).add(in_ns[0]);
+        out_tris[0].ns[2] = in_ns[0].lerp(out_ns[0], tac);
+
+        out_tris[0].ts[0] = in_ts[0];
+        out_tris[0].ts[1] = in_ts[1];
+        out_tris[0].ts[2] = in_ts[0].lerp(out_ts[0], tac);
+
+        out_tris[0].bs[0] = in_bs[0];
+        out_tris[0].bs[1] = in_bs[1];
+        out_tris[0].bs[2] = in_bs[0].lerp(out_bs[0], tac);
+
+        out_tris[0].cols[0] = in_cols[0];
+        out_tris[0].cols[1] = in_cols[1];
+        out_tris[0].cols[2] = in_cols[0].lerp(out_cols[0], tac);
 
         let tab = ab.1;
 
@@ -508,40 +359,111 @@ pub fn clip_tri(
 
         out_tris[1].ns[0] = in_ns[1];
         out_tris[1].ns[1] = out_tris[0].ns[2];
-        out_tris[1].ns[2] = out_ns[0].subtract(in_ns[1]).scale_c(tab).add(in_ns[1]);
+        out_tris[1].ns[2] = in_ns[1].lerp(out_ns[0], tab);
+
+        out_tris[1].ts[0] = in_ts[1];
+        out_tris[1].ts[1] = out_tris[0].ts[2];
+        out_tris[1].ts[2] = in_ts[1].lerp(out_ts[0], tab);
+
+        out_tris[1].bs[0] = in_bs[1];
+        out_tris[1].bs[1] = out_tris[0].bs[2];
+        out_tris[1].bs[2] = in_bs[1].lerp(out_bs[0], tab);
+
+        out_tris[1].cols[0] = in_cols[1];
+        out_tris[1].cols[1] = out_tris[0].cols[2];
+        out_tris[1].cols[2] = in_cols[1].lerp(out_cols[0], tab);
 
         return 2;
     }
     0
 }
+pub fn gen_terrain(start : Point3, end : Point3, spacing : [f32;2], func : &dyn Fn(f32, f32)->f32)->Vec<Point3>{
+    let mut r : Vec<Point3> = Vec::new();
+    for i in start.x as i32..end.x as i32{
+        for j in start.z as i32..end.z as i32{
+            if i%spacing[0] as i32 == 0 && j%spacing[1] as i32 == 0{
+                let x = i as f32;
+                let z = j as f32;
+                let y = func(x, z) + start.y;
+                r.push(Point3::new(x, y, z));
+            }
+
+        }
+    }
+    r
+}
+
 //VERY SLOW AND SHOULD ONLY BE USED ONCE PER OBJECT
 pub fn estimate_normals(mesh: &mut Mesh) {
-    for i in 0..mesh.tris.len() {
-        let tri = mesh.tris[i];
+    let mut tris = mesh.geometry.tris();
+    for i in 0..tris.len() {
+        let tri = tris[i];
         for j in 0..3 {
             let mut norm = tri.normal();
             let point = tri.ps[j];
-            for i1 in 0..mesh.tris.len() {
+            for (i1, tri1) in tris.iter().enumerate() {
                 if i1 != i {
-                    let tri1 = mesh.tris[i1];
                     let mut c = false;
                     for j1 in 0..3 {
                         let point1 = tri1.ps[j1];
-                        if (point[0] - point1[0]).abs() < f32::EPSILON
-                            && (point[1] - point1[1]).abs() < f32::EPSILON
-                            && (point[2] - point1[2]).abs() < f32::EPSILON
+                        if (point.x - point1.x).abs() < f32::EPSILON
+                            && (point.y - point1.y).abs() < f32::EPSILON
+                            && (point.z - point1.z).abs() < f32::EPSILON
                         {
                             c = true;
                         }
                     }
                     if c {
-                        norm = norm.add(tri1.normal());
+                        norm += tri1.normal();
                     }
                 }
             }
-            mesh.tris[i].ns[j] = norm.normalize();
+            tris[i].ns[j] = norm.normalize();
         }
     }
+    //corners that now agree are merged, the old tangents would keep them apart
+    for t in &mut tris {
+        t.ts = [Vec3::ZERO; 3];
+        t.bs = [Vec3::ZERO; 3];
+    }
+    mesh.geometry = IndexedMesh::from_tris(&tris);
+    //keep the tangent frames orthogonal to the new normals
+    mesh.geometry.estimate_tangents();
 }
 
-
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scene::Node;
+
+    #[test]
+    fn unplaced_meshes() {
+        let camera = Camera {
+            fov: 90.0,
+            pos: Point3::ORIGIN,
+            dir: Vec3::Z,
+            vel: Vec3::ZERO,
+            rot_vel: Vec3::ZERO,
+            clip_distance: 0.5,
+            render_distance: 100.0,
+            window_height: 1.0,
+            window_width: 1.0,
+        };
+        let mut engine = Engine::new(camera);
+        engine.objects.push(Mesh::new(Vec::new(), String::new()));
+        engine.objects.push(Mesh::new(Vec::new(), String::new()));
+        let node = Node { transform: Transform::from_pos(Point3::new(1.0, 2.0, 3.0)), ..Node::with_mesh("b", 1) };
+        engine.scene.add(node, None);
+        engine.scene.update();
+
+        let placed = engine.instances();
+        assert_eq!(placed.len(), 2);
+        assert!(std::ptr::eq(placed[0].mesh, &engine.objects[1]));
+        assert_eq!(placed[0].world.transform_point(Point3::ORIGIN), Point3::new(1.0, 2.0, 3.0));
+        assert!(std::ptr::eq(placed[1].mesh, &engine.objects[0]));
+        assert_eq!(placed[1].world, Mat4::IDENTITY);
+
+        engine.draw_unplaced = false;
+        assert_eq!(engine.instances().len(), 1);
+    }
+}